@@ -55,7 +55,10 @@ use log::{debug, info, trace};
 
 use srml_aura::{
 	InherentType as AuraInherent, AuraInherentData,
-	timestamp::{TimestampInherentData, InherentType as TimestampInherent, InherentError as TIError}
+	timestamp::{
+		TimestampInherentData, InherentType as TimestampInherent, InherentError as TIError,
+		MAX_TIMESTAMP_DRIFT,
+	},
 };
 use substrate_telemetry::{telemetry, CONSENSUS_TRACE, CONSENSUS_DEBUG, CONSENSUS_INFO};
 
@@ -421,8 +424,6 @@ impl<C, P, T> AuraVerifier<C, P, T>
 	) -> Result<(), String>
 		where C: ProvideRuntimeApi, C::Api: BlockBuilderApi<B>
 	{
-		const MAX_TIMESTAMP_DRIFT_SECS: u64 = 60;
-
 		let inherent_res = self.client.runtime_api().check_inherents(
 			&block_id,
 			block,
@@ -436,7 +437,7 @@ impl<C, P, T> AuraVerifier<C, P, T>
 					Some(TIError::ValidAtTimestamp(timestamp)) => {
 						// halt import until timestamp is valid.
 						// reject when too far ahead.
-						if timestamp > timestamp_now + MAX_TIMESTAMP_DRIFT_SECS {
+						if timestamp > timestamp_now + MAX_TIMESTAMP_DRIFT {
 							return Err("Rejecting block too far in future".into());
 						}
 
@@ -452,7 +453,20 @@ impl<C, P, T> AuraVerifier<C, P, T>
 						thread::sleep(Duration::from_secs(diff));
 						Ok(())
 					},
+					Some(TIError::AlreadySet) => Err("Timestamp set more than once in the block".into()),
 					Some(TIError::Other(e)) => Err(e.into()),
+					Some(TIError::TooFarInPast { submitted, minimum }) => {
+						// every historical block lags behind the clock while syncing.
+						debug!(
+							target: "aura",
+							"importing block with timestamp {} lagging behind the earliest expected of {}",
+							submitted,
+							minimum,
+						);
+						Ok(())
+					},
+					Some(e @ TIError::TooFarInFuture { .. }) =>
+						Err(format!("Rejecting block with invalid timestamp: {:?}", e)),
 					None => Err(self.inherent_data_providers.error_to_string(&i, &e)),
 				})
 		} else {
@@ -349,6 +349,12 @@ impl From<srml_system::Event> for Event {
 	}
 }
 
+impl From<srml_timestamp::Event<Runtime>> for Event {
+	fn from(_evt: srml_timestamp::Event<Runtime>) -> Self {
+		unimplemented!("Not required in tests!")
+	}
+}
+
 parameter_types! {
 	pub const BlockHashCount: BlockNumber = 250;
 	pub const MinimumPeriod: u64 = 5;
@@ -377,10 +383,13 @@ impl srml_system::Trait for Runtime {
 }
 
 impl srml_timestamp::Trait for Runtime {
+	type Event = Event;
 	/// A timestamp: milliseconds since the unix epoch.
 	type Moment = u64;
 	type OnTimestampSet = ();
 	type MinimumPeriod = MinimumPeriod;
+	type MomentUnit = srml_timestamp::Milliseconds;
+	type Settings = ();
 }
 
 parameter_types! {
@@ -630,6 +639,16 @@ cfg_if! {
 				}
 			}
 
+			impl srml_timestamp::TimestampApi<Block, u64> for Runtime {
+				fn now_millis() -> u64 {
+					<srml_timestamp::Module<Runtime>>::now_as_millis()
+				}
+
+				fn block_period() -> u64 {
+					<srml_timestamp::Module<Runtime>>::block_period()
+				}
+			}
+
 			impl session::SessionKeys<Block> for Runtime {
 				fn generate_session_keys(_: Option<Vec<u8>>) -> Vec<u8> {
 					SessionKeys::generate(None)
@@ -845,6 +864,16 @@ cfg_if! {
 				}
 			}
 
+			impl srml_timestamp::TimestampApi<Block, u64> for Runtime {
+				fn now_millis() -> u64 {
+					<srml_timestamp::Module<Runtime>>::now_as_millis()
+				}
+
+				fn block_period() -> u64 {
+					<srml_timestamp::Module<Runtime>>::block_period()
+				}
+			}
+
 			impl session::SessionKeys<Block> for Runtime {
 				fn generate_session_keys(_: Option<Vec<u8>>) -> Vec<u8> {
 					SessionKeys::generate(None)
@@ -896,7 +925,7 @@ mod tests {
 		generic::BlockId,
 		traits::ProvideRuntimeApi,
 	};
-	use primitives::storage::well_known_keys::HEAP_PAGES;
+	use primitives::{storage::well_known_keys::HEAP_PAGES, twox_128};
 	use state_machine::ExecutionStrategy;
 	use codec::Encode;
 
@@ -946,6 +975,42 @@ mod tests {
 		assert!(ret.is_ok());
 	}
 
+	#[test]
+	fn timestamp_api_returns_now_in_millis() {
+		use srml_timestamp::TimestampApi;
+
+		let client = TestClientBuilder::new()
+			.set_execution_strategy(ExecutionStrategy::AlwaysWasm)
+			.build();
+		let runtime_api = client.runtime_api();
+		let block_id = BlockId::Number(client.info().chain.best_number);
+		assert_eq!(runtime_api.now_millis(&block_id).unwrap(), 0);
+
+		let new_block_id = {
+			let mut builder = client.new_block(Default::default()).unwrap();
+			builder.push_storage_change(twox_128(b"Timestamp Now").to_vec(), Some(42u64.encode())).unwrap();
+			let block = builder.bake().unwrap();
+			let hash = block.header.hash();
+			client.import(BlockOrigin::Own, block).unwrap();
+			BlockId::Hash(hash)
+		};
+		// the test runtime counts `Moment` in milliseconds.
+		assert_eq!(runtime_api.now_millis(&new_block_id).unwrap(), 42);
+	}
+
+	#[test]
+	fn timestamp_api_returns_block_period() {
+		use srml_timestamp::TimestampApi;
+
+		let client = TestClientBuilder::new()
+			.set_execution_strategy(ExecutionStrategy::AlwaysWasm)
+			.build();
+		let runtime_api = client.runtime_api();
+		let block_id = BlockId::Number(client.info().chain.best_number);
+		// the genesis config sets no block period, so it falls back to the minimum period.
+		assert_eq!(runtime_api.block_period(&block_id).unwrap(), 5);
+	}
+
 	#[test]
 	fn heap_pages_is_respected() {
 		// This tests that the on-chain HEAP_PAGES parameter is respected.
@@ -212,10 +212,13 @@ parameter_types! {
 }
 
 impl timestamp::Trait for Runtime {
+	type Event = Event;
 	/// A timestamp: milliseconds since the unix epoch.
 	type Moment = u64;
 	type OnTimestampSet = Babe;
 	type MinimumPeriod = MinimumPeriod;
+	type MomentUnit = timestamp::Milliseconds;
+	type Settings = ();
 }
 
 parameter_types! {
@@ -264,7 +267,7 @@ construct_runtime!(
 		UncheckedExtrinsic = UncheckedExtrinsic
 	{
 		System: system::{Module, Call, Storage, Config, Event},
-		Timestamp: timestamp::{Module, Call, Storage, Inherent},
+		Timestamp: timestamp::{Module, Call, Storage, Event<T>, Inherent},
 		Babe: babe::{Module, Call, Storage, Config, Inherent(Timestamp)},
 		Grandpa: grandpa::{Module, Call, Storage, Config, Event},
 		Indices: indices::{default, Config<T>},
@@ -21,7 +21,7 @@ pub use node_primitives::{AccountId, Balance};
 use node_runtime::{
 	AuthorityDiscoveryConfig, BabeConfig, BalancesConfig, ContractsConfig, CouncilConfig, DemocracyConfig,
 	ElectionsConfig, GrandpaConfig, ImOnlineConfig, IndicesConfig, SessionConfig, SessionKeys, StakerStatus,
-	StakingConfig, SudoConfig, SystemConfig, TechnicalCommitteeConfig, WASM_BINARY,
+	StakingConfig, SudoConfig, SystemConfig, TechnicalCommitteeConfig, TimestampConfig, WASM_BINARY,
 };
 use node_runtime::constants::{time::*, currency::*};
 pub use node_runtime::GenesisConfig;
@@ -164,6 +164,10 @@ fn staging_testnet_config_genesis() -> GenesisConfig {
 		sudo: Some(SudoConfig {
 			key: endowed_accounts[0].clone(),
 		}),
+		timestamp: Some(TimestampConfig {
+			genesis_timestamp: 0,
+			period: SLOT_DURATION,
+		}),
 		babe: Some(BabeConfig {
 			authorities: vec![],
 		}),
@@ -298,6 +302,10 @@ pub fn testnet_genesis(
 		sudo: Some(SudoConfig {
 			key: root_key,
 		}),
+		timestamp: Some(TimestampConfig {
+			genesis_timestamp: 0,
+			period: SLOT_DURATION,
+		}),
 		babe: Some(BabeConfig {
 			authorities: vec![],
 		}),
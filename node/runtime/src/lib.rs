@@ -22,7 +22,7 @@
 
 use rstd::prelude::*;
 use support::{
-	construct_runtime, parameter_types, traits::{SplitTwoWays, Currency}
+	construct_runtime, parameter_types, traits::{SplitTwoWays, Currency, FindAuthor}
 };
 use primitives::u32_trait::{_1, _2, _3, _4};
 use node_primitives::{
@@ -35,7 +35,7 @@ use client::{
 	block_builder::api::{self as block_builder_api, InherentData, CheckInherentsResult},
 	runtime_api as client_api, impl_runtime_apis
 };
-use sr_primitives::{ApplyResult, impl_opaque_keys, generic, create_runtime_str, key_types};
+use sr_primitives::{ApplyResult, ConsensusEngineId, impl_opaque_keys, generic, create_runtime_str, key_types};
 use sr_primitives::transaction_validity::TransactionValidity;
 use sr_primitives::weights::Weight;
 use sr_primitives::traits::{
@@ -79,8 +79,8 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	// and set impl_version to equal spec_version. If only runtime
 	// implementation changes and behavior does not, then leave spec_version as
 	// is and increment impl_version.
-	spec_version: 151,
-	impl_version: 151,
+	spec_version: 188,
+	impl_version: 188,
 	apis: RUNTIME_API_VERSIONS,
 };
 
@@ -173,10 +173,23 @@ impl balances::Trait for Runtime {
 parameter_types! {
 	pub const MinimumPeriod: Moment = SLOT_DURATION / 2;
 }
+/// Records the Babe author of each block as the one who set its timestamp.
+pub struct TimestampSettings;
+impl timestamp::Settings<Runtime> for TimestampSettings {
+	fn find_author<'a, I>(digests: I) -> Option<AccountId>
+		where I: 'a + IntoIterator<Item=(ConsensusEngineId, &'a [u8])>
+	{
+		session::FindAccountFromAuthorIndex::<Runtime, Babe>::find_author(digests)
+	}
+}
+
 impl timestamp::Trait for Runtime {
+	type Event = Event;
 	type Moment = Moment;
 	type OnTimestampSet = Babe;
 	type MinimumPeriod = MinimumPeriod;
+	type MomentUnit = timestamp::Milliseconds;
+	type Settings = TimestampSettings;
 }
 
 parameter_types! {
@@ -431,7 +444,7 @@ construct_runtime!(
 	{
 		System: system::{Module, Call, Storage, Config, Event},
 		Babe: babe::{Module, Call, Storage, Config, Inherent(Timestamp)},
-		Timestamp: timestamp::{Module, Call, Storage, Inherent},
+		Timestamp: timestamp::{Module, Call, Storage, Config<T>, Event<T>, Inherent},
 		Authorship: authorship::{Module, Call, Storage, Inherent},
 		Indices: indices,
 		Balances: balances,
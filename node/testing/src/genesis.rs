@@ -20,9 +20,9 @@ use crate::keyring::*;
 use keyring::{Ed25519Keyring, Sr25519Keyring};
 use node_runtime::{
 	GenesisConfig, BalancesConfig, SessionConfig, StakingConfig, SystemConfig,
-	GrandpaConfig, IndicesConfig, ContractsConfig, WASM_BINARY,
+	GrandpaConfig, IndicesConfig, ContractsConfig, TimestampConfig, WASM_BINARY,
 };
-use node_runtime::constants::currency::*;
+use node_runtime::constants::{time::*, currency::*};
 use primitives::ChangesTrieConfiguration;
 use sr_primitives::Perbill;
 
@@ -84,6 +84,10 @@ pub fn config(support_changes_trie: bool, code: Option<&[u8]>) -> GenesisConfig
 			current_schedule: Default::default(),
 			gas_price: 1 * MILLICENTS,
 		}),
+		timestamp: Some(TimestampConfig {
+			genesis_timestamp: 0,
+			period: SLOT_DURATION,
+		}),
 		babe: Some(Default::default()),
 		grandpa: Some(GrandpaConfig {
 			authorities: vec![],
@@ -64,9 +64,12 @@ impl system::Trait for Test {
 }
 
 impl timestamp::Trait for Test {
+	type Event = ();
 	type Moment = u64;
 	type OnTimestampSet = Aura;
 	type MinimumPeriod = MinimumPeriod;
+	type MomentUnit = timestamp::Milliseconds;
+	type Settings = ();
 }
 
 impl Trait for Test {
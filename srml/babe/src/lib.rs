@@ -93,7 +93,7 @@ impl ProvideInherentData for InherentDataProvider {
 	) -> result::Result<(), RuntimeString> {
 		if !providers.has_provider(&timestamp::INHERENT_IDENTIFIER) {
 			// Add the timestamp inherent data provider, as we require it.
-			providers.register_provider(timestamp::InherentDataProvider)
+			providers.register_provider(timestamp::InherentDataProvider::default())
 		} else {
 			Ok(())
 		}
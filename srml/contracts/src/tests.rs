@@ -53,7 +53,7 @@ mod contract {
 }
 impl_outer_event! {
 	pub enum MetaEvent for Test {
-		balances<T>, contract<T>,
+		balances<T>, contract<T>, timestamp<T>,
 	}
 }
 impl_outer_origin! {
@@ -140,9 +140,12 @@ parameter_types! {
 	pub const MinimumPeriod: u64 = 1;
 }
 impl timestamp::Trait for Test {
+	type Event = MetaEvent;
 	type Moment = u64;
 	type OnTimestampSet = ();
 	type MinimumPeriod = MinimumPeriod;
+	type MomentUnit = timestamp::Milliseconds;
+	type Settings = ();
 }
 parameter_types! {
 	pub const SignedClaimHandicap: u64 = 2;
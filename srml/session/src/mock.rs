@@ -164,9 +164,12 @@ impl system::Trait for Test {
 }
 
 impl timestamp::Trait for Test {
+	type Event = ();
 	type Moment = u64;
 	type OnTimestampSet = ();
 	type MinimumPeriod = MinimumPeriod;
+	type MomentUnit = timestamp::Milliseconds;
+	type Settings = ();
 }
 
 impl Trait for Test {
@@ -178,9 +178,12 @@ parameter_types! {
 	pub const MinimumPeriod: u64 = 5;
 }
 impl timestamp::Trait for Test {
+	type Event = ();
 	type Moment = u64;
 	type OnTimestampSet = ();
 	type MinimumPeriod = MinimumPeriod;
+	type MomentUnit = timestamp::Milliseconds;
+	type Settings = ();
 }
 parameter_types! {
 	pub const SessionsPerEra: SessionIndex = 3;
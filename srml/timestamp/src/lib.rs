@@ -39,15 +39,71 @@
 //! ### Dispatchable Functions
 //!
 //! * `set` - Sets the current time.
+//! * `set_with_context` - Sets the current time like `set`, also recording the consensus slot of
+//! the block.
+//! * `set_block_period` - Sets the advised period between blocks. Root only.
+//! * `schedule_block_period_change` - Schedules a change of the advised period between blocks at a
+//! future block. Root only.
+//! * `force_clear_did_update` - Clears the flag recording that the timestamp was set in the
+//! current block. Root only.
 //!
 //! ### Public functions
 //!
 //! * `get` - Gets the current time for the current block. If this function is called prior to
 //! setting the timestamp, it will return the timestamp of the previous block.
+//! * `try_now` - Gets the current time if it has already been set in the current block.
+//! * `now_finalized` - Gets the current time, panicking if it hasn't been set yet in the current
+//! block.
+//! * `try_set` - Sets the current time like the `set` inherent, but returns an error instead of
+//! panicking if the timestamp is invalid.
+//! * `average_block_time` - Gets the average interval between the most recent blocks.
+//! * `moment_to_duration`, `duration_to_moment` - Convert between moments and `Duration`s, given
+//! the `MomentUnit` of the chain. Only available in `std` builds.
+//! * `duration_between` - Gets the time between two moments, or zero if the end is before the start.
+//! * `elapsed` - Gets the observed time between the previous block and the current one.
+//! * `expected_timestamp` - Gets the timestamp a block would have if blocks were produced exactly
+//! `block_period` apart since genesis.
+//! * `historical_timestamp` - Gets the timestamp of one of the last `Settings::history_depth` blocks.
+//! * `was_updated_at` - Checks whether one of the last `Settings::history_depth` blocks set its timestamp.
+//! * `is_expired` - Checks whether a deadline has been reached.
+//! * `minimum_valid_timestamp` - Gets the smallest timestamp the next block may have.
+//! * `median_timestamp` - Gets the median of the recently set timestamps.
+//! * `now_plus` - Gets the current time plus a duration, saturating at the maximum moment.
+//! * `now_as_millis` - Gets the current time in milliseconds, given the `MomentUnit` of the chain.
+//! * `now_as_secs` - Gets the current time in seconds, given the `MomentUnit` of the chain.
+//! * `to_unix_secs`, `from_unix_secs` - Convert between moments and seconds since the unix epoch,
+//! given the `MomentUnit` of the chain.
+//! * `now_as_datetime` - Gets the current time as a calendar date and time, assuming the chain
+//! counts in seconds. Only available in `std` builds.
+//! * `time_until` - Gets the time remaining until a deadline.
+//! * `blocks_until` - Estimates the number of blocks until a time at the advised block period.
 //!
 //! ### Trait Getters
 //!
-//! * `MinimumPeriod` - Gets the minimum (and advised) period between blocks for the chain.
+//! * `MinimumPeriod` - Gets the minimum period between blocks for the chain.
+//! * `MomentUnit` - Gets the number of milliseconds in one `Moment`.
+//!
+//! Everything else is optional and comes from `Settings`, whose defaults suit most chains, e.g.:
+//!
+//! * `Settings::past_tolerance` - Gets how far below `MinimumPeriod` a new timestamp may still be
+//! accepted.
+//! * `Settings::max_timestamp_drift` - Gets the maximum amount a block's timestamp may run ahead of
+//! the local clock.
+//! * `Settings::max_past_drift_periods` - Gets how many block periods a block's timestamp may lag
+//! behind the local clock before it is reported as too far in the past.
+//!
+//! The period enforced between blocks comes from the `MinimumPeriod` constant, so checking it needs
+//! no storage read. Chains whose period may change at runtime can additionally advise block authors
+//! of a longer period through the `BlockPeriod` storage item, using `set_block_period`.
+//!
+//! ### Module Getters
+//!
+//! * `block_period` - Gets the advised period between blocks, used when authoring the timestamp
+//! inherent. It is never less than `MinimumPeriod`.
+//! * `minimum_period` - Gets the minimum period between blocks enforced by `set`.
+//! * `is_updated` - Checks whether the timestamp has already been set in the current block.
+//! * `reported_timestamps` - Gets the last `Settings::reported_timestamps_window` timestamps, oldest first,
+//! e.g. to compare the timestamps accepted on competing forks.
 //!
 //! ## Usage
 //!
@@ -79,6 +135,25 @@
 //! # fn main() {}
 //! ```
 //!
+//! ### Depend on the time without the Timestamp module
+//!
+//! The module implements the `Time` trait of `srml_support`, so modules that only read the
+//! current time can be generic over a `Time` type instead, like the Staking module, and be tested
+//! with any other implementation.
+//!
+//! ```
+//! use srml_support::traits::Time;
+//!
+//! pub trait Trait: system::Trait {
+//! 	type Time: Time;
+//! }
+//!
+//! fn is_expired<T: Trait>(deadline: <T::Time as Time>::Moment) -> bool {
+//! 	T::Time::now() >= deadline
+//! }
+//! # fn main() {}
+//! ```
+//!
 //! ### Example from the SRML
 //!
 //! The [Session module](https://github.com/paritytech/substrate/blob/master/srml/session/src/lib.rs) uses
@@ -90,54 +165,130 @@
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
-use rstd::{result, cmp};
+use rstd::{prelude::*, result, cmp};
 use codec::Encode;
 #[cfg(feature = "std")]
 use codec::Decode;
 #[cfg(feature = "std")]
 use inherents::ProvideInherentData;
-use srml_support::{StorageValue, Parameter, decl_storage, decl_module, for_each_tuple};
+#[cfg(feature = "std")]
+use std::sync::{Mutex, atomic::{AtomicU64, Ordering}};
+#[cfg(feature = "std")]
+use std::time::{Duration, SystemTime};
+use srml_support::{
+	StorageValue, StorageMap, Parameter, decl_event, decl_storage, decl_module, ensure, for_each_tuple,
+};
 use srml_support::traits::{Time, Get};
+use srml_support::{Twox128, storage::hashed::generator::StorageHasher};
 use sr_primitives::traits::{
-	SimpleArithmetic, Zero, SaturatedConversion, Scale
+	SimpleArithmetic, Zero, One, Saturating, CheckedAdd, CheckedMul, CheckedDiv, Bounded, SaturatedConversion, Scale,
+	Hash,
 };
-use sr_primitives::weights::SimpleDispatchInfo;
-use system::ensure_none;
+use sr_primitives::ConsensusEngineId;
+use sr_primitives::weights::{SimpleDispatchInfo, Weight};
+use system::{ensure_none, ensure_root};
 use inherents::{RuntimeString, InherentIdentifier, ProvideInherent, IsFatalError, InherentData};
 
+#[cfg(feature = "std")]
+mod ntp;
+#[cfg(feature = "std")]
+mod uptime;
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod tests;
+
+#[cfg(feature = "std")]
+pub use ntp::NtpClock;
+#[cfg(feature = "std")]
+pub use uptime::{UptimeInherentData, UptimeInherentDataProvider};
+
 /// The identifier for the `timestamp` inherent.
 pub const INHERENT_IDENTIFIER: InherentIdentifier = *b"timstap0";
 /// The type of the inherent.
 pub type InherentType = u64;
+/// The identifier for the uptime of the authoring node, optionally provided along with the
+/// timestamp inherent data. The runtime ignores it.
+pub const UPTIME_INHERENT_IDENTIFIER: InherentIdentifier = *b"timstup0";
+/// The weight of the `set` call: reading the current timestamp, writing the new one and the update
+/// flag, and depositing the event. A placeholder until it is benchmarked.
+pub const SET_WEIGHT: Weight = 10_000;
+/// The recommended `Settings::max_timestamp_drift`, in the unit of the inherent data. Client-side
+/// tooling building blocks can use it to stay within what runtimes using the default accept.
+pub const MAX_TIMESTAMP_DRIFT: InherentType = 60;
 
 /// Errors that can occur while checking the timestamp inherent.
 #[derive(Encode)]
-#[cfg_attr(feature = "std", derive(Debug, Decode))]
+#[cfg_attr(feature = "std", derive(Debug, Clone, Decode, PartialEq))]
 pub enum InherentError {
 	/// The timestamp is valid in the future.
 	/// This is a non-fatal-error and will not stop checking the inherents.
 	ValidAtTimestamp(InherentType),
 	/// Some other error.
 	Other(RuntimeString),
+	/// The timestamp `submitted` runs ahead of the local clock by more than the accepted drift,
+	/// i.e. it is after `max_allowed`.
+	TooFarInFuture { submitted: InherentType, max_allowed: InherentType },
+	/// The timestamp `submitted` lags behind the local clock by more than
+	/// `Settings::max_past_drift_periods` block periods, i.e. it is before `minimum`. It is only
+	/// reported for a timestamp passing every other check.
+	/// This is a non-fatal-error: a node syncing historical blocks sees every one of them lag far
+	/// behind its clock, so only a consensus engine knowing it is at the head of the chain should
+	/// reject the block for it.
+	TooFarInPast { submitted: InherentType, minimum: InherentType },
+	/// The timestamp was already set in this block.
+	AlreadySet,
 }
 
 impl IsFatalError for InherentError {
 	fn is_fatal_error(&self) -> bool {
 		match self {
 			InherentError::ValidAtTimestamp(_) => false,
+			InherentError::AlreadySet => true,
 			InherentError::Other(_) => true,
+			InherentError::TooFarInFuture { .. } => true,
+			InherentError::TooFarInPast { .. } => false,
 		}
 	}
 }
 
+/// Why an `InherentError` could not be decoded.
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq)]
+pub enum InherentErrorDecodeError {
+	/// The error belongs to another inherent.
+	WrongIdentifier,
+	/// The data is not a valid `InherentError`, with `remaining` bytes left where decoding failed.
+	Undecodable { remaining: usize },
+}
+
 impl InherentError {
 	/// Try to create an instance ouf of the given identifier and data.
 	#[cfg(feature = "std")]
 	pub fn try_from(id: &InherentIdentifier, data: &[u8]) -> Option<Self> {
-		if id == &INHERENT_IDENTIFIER {
-			<InherentError as codec::Decode>::decode(&mut &data[..]).ok()
-		} else {
-			None
+		Self::decode_from(id, data).ok()
+	}
+
+	/// Try to create an instance out of the given identifier and data, reporting why it failed.
+	#[cfg(feature = "std")]
+	pub fn decode_from(id: &InherentIdentifier, data: &[u8]) -> Result<Self, InherentErrorDecodeError> {
+		if id != &INHERENT_IDENTIFIER {
+			return Err(InherentErrorDecodeError::WrongIdentifier);
+		}
+
+		let mut input = data;
+		<InherentError as codec::Decode>::decode(&mut input)
+			.map_err(|_| InherentErrorDecodeError::Undecodable { remaining: input.len() })
+	}
+
+	/// Describe the timestamp inherent error encoded in `data`, including a diagnostic if the
+	/// data is corrupted.
+	#[cfg(feature = "std")]
+	fn describe(data: &[u8]) -> Option<String> {
+		match Self::decode_from(&INHERENT_IDENTIFIER, data) {
+			Ok(error) => Some(format!("{:?}", error)),
+			Err(InherentErrorDecodeError::WrongIdentifier) => None,
+			Err(error) => Some(format!("Corrupted timestamp inherent error: {:?}", error)),
 		}
 	}
 }
@@ -146,6 +297,12 @@ impl InherentError {
 pub trait TimestampInherentData {
 	/// Get timestamp inherent data.
 	fn timestamp_inherent_data(&self) -> Result<InherentType, RuntimeString>;
+
+	/// Get timestamp inherent data in milliseconds, given the `unit` it is provided in as the
+	/// number of milliseconds in one unit, e.g. `Seconds::get()`. Saturates at `u64::max_value()`.
+	fn timestamp_inherent_data_millis(&self, unit: u64) -> Result<InherentType, RuntimeString> {
+		self.timestamp_inherent_data().map(|moment| moment.saturating_mul(unit))
+	}
 }
 
 impl TimestampInherentData for InherentData {
@@ -155,8 +312,281 @@ impl TimestampInherentData for InherentData {
 	}
 }
 
+/// A source of the current time for `InherentDataProvider`.
+#[cfg(feature = "std")]
+pub trait ClockSource: Send + Sync {
+	/// The time elapsed since the unix epoch.
+	fn now_since_epoch(&self) -> Result<Duration, RuntimeString>;
+}
+
+/// The environment variable overriding the time read by `SystemClock`, in seconds since the unix
+/// epoch.
+///
+/// This is only meant for testing and reproducible deployments, e.g. in containers: a node
+/// authoring blocks with it set proposes the same timestamp for every block, so the chain stalls
+/// once that timestamp is in the past.
+#[cfg(feature = "std")]
+pub const TIMESTAMP_OVERRIDE_ENV: &str = "SUBSTRATE_TIMESTAMP_OVERRIDE";
+
+/// The system clock, unless overridden by the `TIMESTAMP_OVERRIDE_ENV` environment variable.
+#[cfg(feature = "std")]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl ClockSource for SystemClock {
+	fn now_since_epoch(&self) -> Result<Duration, RuntimeString> {
+		if let Some(secs) = std::env::var_os(TIMESTAMP_OVERRIDE_ENV) {
+			return parse_timestamp_override(&secs);
+		}
+
+		SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)
+			.map_err(|_| "Current time is before unix epoch".into())
+	}
+}
+
+/// Parse the value of the `TIMESTAMP_OVERRIDE_ENV` environment variable.
+#[cfg(feature = "std")]
+fn parse_timestamp_override(secs: &std::ffi::OsStr) -> Result<Duration, RuntimeString> {
+	secs.to_str()
+		.and_then(|secs| secs.parse().ok())
+		.map(Duration::from_secs)
+		.ok_or_else(|| format!("{} must be a unix timestamp in seconds", TIMESTAMP_OVERRIDE_ENV).into())
+}
+
+/// The unix timestamps, in seconds, of the leap seconds inserted since 2000, i.e. the midnight
+/// following each `23:59:60` UTC. Extend this when a new leap second is announced.
+#[cfg(feature = "std")]
+const LEAP_SECONDS: [u64; 5] = [1_136_073_600, 1_230_768_000, 1_341_100_800, 1_435_708_800, 1_483_228_800];
+
+/// The length of the window, centered on a leap second, over which smearing clocks spread it.
+#[cfg(feature = "std")]
+const LEAP_SMEAR_WINDOW_MILLIS: u128 = 24 * 60 * 60 * 1000;
+
+/// How `InherentDataProvider` treats the clock around leap seconds.
+///
+/// Unix time repeats the leap second, so a stepping clock stalls the provided timestamp for a
+/// second, as it never goes backwards. A smearing clock instead runs slow over the 24 hours
+/// around the leap second and deviates from unix time by up to half a second meanwhile.
 #[cfg(feature = "std")]
-pub struct InherentDataProvider;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeapSecondPolicy {
+	/// Report the clock as it is. Nodes with stepping and smearing clocks disagree by up to half
+	/// a second around leap seconds.
+	Ignore,
+	/// The clock is smeared linearly over the 24 hours around a leap second, as some NTP servers
+	/// do; report the unix time a stepping clock would show instead, so all nodes agree, at the
+	/// cost of stalling for the leap second.
+	Smear,
+}
+
+#[cfg(feature = "std")]
+impl Default for LeapSecondPolicy {
+	fn default() -> Self {
+		LeapSecondPolicy::Ignore
+	}
+}
+
+#[cfg(feature = "std")]
+impl LeapSecondPolicy {
+	/// Normalize the clock reading `since_epoch` to unix time according to the policy.
+	fn normalize(self, since_epoch: Duration) -> Duration {
+		if self == LeapSecondPolicy::Ignore {
+			return since_epoch;
+		}
+
+		let smeared = since_epoch.as_millis();
+		for leap in LEAP_SECONDS.iter().map(|secs| u128::from(*secs) * 1000) {
+			let start = leap - LEAP_SMEAR_WINDOW_MILLIS / 2;
+			// the smeared clock ends the window a second behind the time elapsed.
+			let end = leap + LEAP_SMEAR_WINDOW_MILLIS / 2 - 1000;
+			if smeared >= start && smeared < end {
+				let elapsed = (smeared - start) * LEAP_SMEAR_WINDOW_MILLIS / (LEAP_SMEAR_WINDOW_MILLIS - 1000);
+				let actual = start + elapsed;
+				let unix = if actual < leap { actual } else { actual - 1000 };
+				return Duration::from_millis(unix as u64);
+			}
+		}
+		since_epoch
+	}
+}
+
+/// The tallies of the outcomes of checking timestamp inherents, as recorded with
+/// `InherentDataProvider::record_check`.
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CheckOutcomes {
+	/// The number of accepted timestamps.
+	pub ok: u64,
+	/// The number of timestamps that are only valid later, i.e. `InherentError::ValidAtTimestamp`.
+	pub valid_at_timestamp: u64,
+	/// The number of timestamps rejected for good with any other error.
+	pub rejected: u64,
+}
+
+/// The last decision of checking a timestamp inherent, as recorded with
+/// `InherentDataProvider::record_check_decision`, for diagnosing rejected blocks.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckDecision {
+	/// The timestamp submitted in the block.
+	pub submitted: InherentType,
+	/// The local clock of the node checking the block.
+	pub now: InherentType,
+	/// The advised period between blocks at the block.
+	pub period: InherentType,
+	/// The outcome of the check.
+	pub outcome: Result<(), InherentError>,
+}
+
+/// Provides the timestamp inherent from a `ClockSource`, the system clock by default.
+///
+/// The timestamp is in milliseconds since the unix epoch by default, or in seconds if the
+/// provider is created with `new(false)`. The unit must match the unit of the chain's `Moment`.
+///
+/// The provided timestamp never decreases, even if the clock is stepped backwards.
+#[cfg(feature = "std")]
+pub struct InherentDataProvider {
+	/// Whether the timestamp is in milliseconds rather than seconds.
+	millis: bool,
+	/// The highest timestamp provided so far.
+	last_provided: AtomicU64,
+	/// Where the current time is read from.
+	clock: Box<dyn ClockSource>,
+	/// The offset added to the current time, in the unit of the provided timestamp.
+	future_bias: InherentType,
+	/// How the clock is treated around leap seconds.
+	leap_second_policy: LeapSecondPolicy,
+	/// The latest timestamp that may be provided, in the unit of the provided timestamp.
+	ceiling: Option<InherentType>,
+	/// The tallies of `CheckOutcomes::ok`, `valid_at_timestamp` and `rejected`, in that order.
+	check_outcomes: [AtomicU64; 3],
+	/// The drift recorded by the last call to `record_drift`.
+	last_drift: Mutex<Option<i64>>,
+	/// The decision recorded by the last call to `record_check_decision`.
+	last_decision: Mutex<Option<CheckDecision>>,
+}
+
+#[cfg(feature = "std")]
+impl Default for InherentDataProvider {
+	fn default() -> Self {
+		Self::new(true)
+	}
+}
+
+#[cfg(feature = "std")]
+impl InherentDataProvider {
+	/// Create a provider with millisecond precision if `millis` is set, or with second
+	/// precision otherwise.
+	pub fn new(millis: bool) -> Self {
+		Self::with_clock(millis, SystemClock)
+	}
+
+	/// Create a provider reading the current time from `clock`, e.g. a fixed clock in tests.
+	pub fn with_clock<C: ClockSource + 'static>(millis: bool, clock: C) -> Self {
+		InherentDataProvider {
+			millis,
+			last_provided: AtomicU64::new(0),
+			clock: Box::new(clock),
+			future_bias: 0,
+			leap_second_policy: LeapSecondPolicy::default(),
+			ceiling: None,
+			check_outcomes: Default::default(),
+			last_drift: Mutex::new(None),
+			last_decision: Mutex::new(None),
+		}
+	}
+
+	/// Tally the outcome `result` of checking a timestamp inherent, e.g. when re-checking blocks
+	/// locally for telemetry, and return it unchanged.
+	pub fn record_check(&self, result: Result<(), InherentError>) -> Result<(), InherentError> {
+		let index = match result {
+			Ok(()) => 0,
+			Err(InherentError::ValidAtTimestamp(_)) => 1,
+			Err(_) => 2,
+		};
+		self.check_outcomes[index].fetch_add(1, Ordering::Relaxed);
+		result
+	}
+
+	/// Like `record_check`, also keeping the check of the timestamp `submitted` against the local
+	/// clock `now` at the advised block period `period` as the last decision, for logging.
+	pub fn record_check_decision(
+		&self,
+		submitted: InherentType,
+		now: InherentType,
+		period: InherentType,
+		result: Result<(), InherentError>,
+	) -> Result<(), InherentError> {
+		let decision = CheckDecision { submitted, now, period, outcome: result.clone() };
+		*self.last_decision.lock().expect("the decision is only ever assigned; qed") = Some(decision);
+		self.record_check(result)
+	}
+
+	/// The decision recorded by the last call to `record_check_decision`, if any.
+	pub fn last_check_decision(&self) -> Option<CheckDecision> {
+		self.last_decision.lock().expect("the decision is only ever assigned; qed").clone()
+	}
+
+	/// The tallies of the checks recorded with `record_check` so far.
+	pub fn check_outcomes(&self) -> CheckOutcomes {
+		let tally = |index: usize| self.check_outcomes[index].load(Ordering::Relaxed);
+		CheckOutcomes { ok: tally(0), valid_at_timestamp: tally(1), rejected: tally(2) }
+	}
+
+	/// Record the drift of the timestamp `submitted` in a block from the local clock `local`, both
+	/// in the unit of the inherent data, e.g. when checking the inherent, for `reported_drift`.
+	pub fn record_drift(&self, submitted: InherentType, local: InherentType) {
+		let drift = i128::from(submitted) - i128::from(local);
+		let drift = cmp::max(cmp::min(drift, i64::max_value().into()), i64::min_value().into()) as i64;
+		*self.last_drift.lock().expect("the drift is only ever assigned; qed") = Some(drift);
+	}
+
+	/// The drift recorded by the last call to `record_drift`: how far the submitted timestamp ran
+	/// ahead of the local clock, or behind it if negative. `None` if no drift was recorded yet.
+	pub fn reported_drift(&self) -> Option<i64> {
+		*self.last_drift.lock().expect("the drift is only ever assigned; qed")
+	}
+
+	/// Bias the provided timestamp `secs` seconds into the future, e.g. to make rejections of
+	/// timestamps in the past after a reorg less likely. The bias is capped below
+	/// `MAX_TIMESTAMP_DRIFT` seconds, so it can't make runtimes accepting the recommended drift
+	/// reject the timestamp.
+	pub fn with_future_bias_secs(mut self, secs: u64) -> Self {
+		let unit = if self.millis { 1000 } else { 1 };
+		let cap = MAX_TIMESTAMP_DRIFT.saturating_mul(unit).saturating_sub(1);
+		self.future_bias = cmp::min(secs.saturating_mul(unit), cap);
+		self
+	}
+
+	/// Treat the clock around leap seconds according to `policy`, rather than
+	/// `LeapSecondPolicy::Ignore`.
+	pub fn with_leap_second_policy(mut self, policy: LeapSecondPolicy) -> Self {
+		self.leap_second_policy = policy;
+		self
+	}
+
+	/// Never provide a timestamp after `secs` seconds since the unix epoch, e.g. the scheduled
+	/// sunset of a chain. The provided timestamp is clamped to it, and no timestamp at all is
+	/// provided once the clock is past it, so no more blocks can be authored.
+	pub fn with_ceiling_secs(mut self, secs: u64) -> Self {
+		self.ceiling = Some(if self.millis { secs.saturating_mul(1000) } else { secs });
+		self
+	}
+
+	/// Clamp `now` to be at least the last provided timestamp and remember the result.
+	fn monotonic(&self, now: InherentType) -> InherentType {
+		let mut last = self.last_provided.load(Ordering::Relaxed);
+		loop {
+			if now <= last {
+				return last;
+			}
+			match self.last_provided.compare_exchange_weak(last, now, Ordering::Relaxed, Ordering::Relaxed) {
+				Ok(_) => return now,
+				Err(current) => last = current,
+			}
+		}
+	}
+}
 
 #[cfg(feature = "std")]
 impl ProvideInherentData for InherentDataProvider {
@@ -165,20 +595,74 @@ impl ProvideInherentData for InherentDataProvider {
 	}
 
 	fn provide_inherent_data(&self, inherent_data: &mut InherentData) -> Result<(), RuntimeString> {
-		use std::time::SystemTime;
-
-		let now = SystemTime::now();
-		now.duration_since(SystemTime::UNIX_EPOCH)
-			.map_err(|_| {
-				"Current time is before unix epoch".into()
-			}).and_then(|d| {
-				let duration: InherentType = d.as_millis() as u64;
+		self.clock.now_since_epoch()
+			.map(|d| self.leap_second_policy.normalize(d))
+			.and_then(|d| {
+				let now = if self.millis {
+					cmp::min(d.as_millis(), u64::max_value() as u128) as u64
+				} else {
+					d.as_secs()
+				};
+				let mut target = now.saturating_add(self.future_bias);
+				if let Some(ceiling) = self.ceiling {
+					if now > ceiling {
+						return Err("Current time is past the timestamp ceiling".into());
+					}
+					target = cmp::min(target, ceiling);
+				}
+				let duration: InherentType = self.monotonic(target);
 				inherent_data.put_data(INHERENT_IDENTIFIER, &duration)
 			})
 	}
 
 	fn error_to_string(&self, error: &[u8]) -> Option<String> {
-		InherentError::try_from(&INHERENT_IDENTIFIER, error).map(|e| format!("{:?}", e))
+		InherentError::describe(error)
+	}
+}
+
+/// The encoded timestamp inherent data for `moment`, exactly as `InherentDataProvider` puts it
+/// into the `InherentData` under `INHERENT_IDENTIFIER`.
+#[cfg(feature = "std")]
+pub fn encode_timestamp_inherent(moment: InherentType) -> Vec<u8> {
+	moment.encode()
+}
+
+/// The number of block periods without a new timestamp after which `is_chain_stalled` considers
+/// the chain stalled.
+#[cfg(feature = "std")]
+pub const STALLED_BLOCK_PERIODS: u64 = 10;
+
+/// Whether a chain whose last timestamp is `last_set` looks stalled at the wall clock time `now`,
+/// i.e. no block was produced for more than `STALLED_BLOCK_PERIODS` times `block_period`. All
+/// values are in the unit of the inherent data.
+#[cfg(feature = "std")]
+pub fn is_chain_stalled(last_set: u64, now: u64, block_period: u64) -> bool {
+	now.saturating_sub(last_set) > block_period.saturating_mul(STALLED_BLOCK_PERIODS)
+}
+
+client::decl_runtime_apis! {
+	/// The API to query the timestamp of a block.
+	pub trait TimestampApi<Moment: codec::Codec> {
+		/// The timestamp of the block in milliseconds, i.e. `Module::now_as_millis`.
+		fn now_millis() -> u64;
+		/// The advised period between blocks, i.e. `Module::block_period`.
+		fn block_period() -> Moment;
+	}
+}
+
+/// The unit of a `Moment` counting seconds, as the number of milliseconds in one moment.
+pub struct Seconds;
+impl Get<u64> for Seconds {
+	fn get() -> u64 {
+		1000
+	}
+}
+
+/// The unit of a `Moment` counting milliseconds, as the number of milliseconds in one moment.
+pub struct Milliseconds;
+impl Get<u64> for Milliseconds {
+	fn get() -> u64 {
+		1
 	}
 }
 
@@ -205,8 +689,206 @@ macro_rules! impl_timestamp_set {
 
 for_each_tuple!(impl_timestamp_set);
 
+/// An `OnTimestampSet` hook recording in `MaxLag` the largest lag of block production behind
+/// schedule, i.e. how far a timestamp is past the one expected at its block by
+/// `Module::expected_timestamp`.
+///
+/// Timestamps before the expected one, i.e. of blocks produced ahead of schedule, count as no
+/// lag at all.
+pub struct LagRecorder<T>(rstd::marker::PhantomData<T>);
+
+impl<T: Trait> OnTimestampSet<T::Moment> for LagRecorder<T> {
+	fn on_timestamp_set(moment: T::Moment) {
+		let expected = <Module<T>>::expected_timestamp(<system::Module<T>>::block_number());
+		let lag = moment.saturating_sub(expected);
+		if lag > <Module<T>>::max_lag() {
+			<MaxLag<T>>::put(lag);
+		}
+	}
+}
+
+/// The optional settings and hooks of the module, each with a default suiting most chains, so a
+/// runtime only overrides what it needs. Set `Trait::Settings` to `()` to keep every default.
+pub trait Settings<T: Trait> {
+	/// The index of the extrinsic the `set` inherent is expected at, if any. Setting the
+	/// timestamp at another index deposits `UnexpectedSetPosition`. Defaults to `None`.
+	fn expected_set_position() -> Option<u32> {
+		None
+	}
+
+	/// The timestamp block authors propose in the inherent, given the local clock `data`, the
+	/// current timestamp `now` and the targeted period between blocks `period`. The proposal is
+	/// raised to `Module::minimum_valid_timestamp` if it is lower. Defaults to the local clock, or
+	/// `period` after `now` if that is later; return `now + period` alone for deterministic
+	/// timestamps ignoring the local clock.
+	fn inherent_target(data: T::Moment, now: T::Moment, period: T::Moment) -> T::Moment {
+		cmp::max(data, now.saturating_add(period))
+	}
+
+	/// Called right before the timestamp is set, with both the old and the new timestamp.
+	fn on_before_timestamp_set(_old: T::Moment, _new: T::Moment) {}
+
+	/// Called when the timestamp is set, along with the number of the block it was set in.
+	fn on_timestamp_set_with_context(_moment: T::Moment, _block_number: T::BlockNumber) {}
+
+	/// Called when the timestamp is set; its errors are logged rather than aborting the block, so
+	/// a hook can report a problem without panicking.
+	fn try_on_timestamp_set(_moment: T::Moment) -> result::Result<(), &'static str> {
+		Ok(())
+	}
+
+	/// The maximum amount, in the unit of the inherent data, by which a block's timestamp may run
+	/// ahead of the local clock of the node checking it before the block is rejected. Defaults to
+	/// `MAX_TIMESTAMP_DRIFT`.
+	fn max_timestamp_drift() -> u64 {
+		MAX_TIMESTAMP_DRIFT
+	}
+
+	/// Whether to accept timestamps however far they run ahead of the local clock, ignoring
+	/// `max_timestamp_drift`. Only enable this on permissioned chains whose block authors are
+	/// trusted: nothing else stops an author from pushing the timestamp arbitrarily far into the
+	/// future, after which the chain stalls until the clocks of the other authors catch up.
+	/// Defaults to `false`.
+	fn disable_future_drift_check() -> bool {
+		false
+	}
+
+	/// The number of `block_period()`s by which a block's timestamp may lag behind the local clock
+	/// of the node checking it before it is reported as too far in the past. The report is not
+	/// fatal, as every historical block lags behind the clock of a syncing node. Defaults to zero,
+	/// never reporting old timestamps.
+	fn max_past_drift_periods() -> u32 {
+		0
+	}
+
+	/// Whether to reject for good a timestamp implying a slot before the current block, i.e. one
+	/// earlier than if every block since genesis was produced `block_period()` after its parent,
+	/// as slot-based consensus can't produce more blocks than slots. Defaults to `false`.
+	fn reject_past_slots() -> bool {
+		false
+	}
+
+	/// The number of recently set timestamps to keep in `ReportedTimestamps`, for computing the
+	/// median timestamp and for debugging forks. Defaults to zero, keeping none.
+	fn reported_timestamps_window() -> u32 {
+		0
+	}
+
+	/// The largest amount, in the unit of the inherent data, by which the accepted future drift
+	/// may widen beyond `max_timestamp_drift`, following the average of the recent
+	/// `RecentDrifts`. Defaults to zero, always accepting exactly `max_timestamp_drift`.
+	fn max_adaptive_drift() -> u64 {
+		0
+	}
+
+	/// The number of recent drift samples averaged for `max_adaptive_drift`. Defaults to zero.
+	fn adaptive_drift_window() -> u32 {
+		0
+	}
+
+	/// The number of most recent blocks whose timestamp is kept in `HistoricalTimestamps`.
+	/// Defaults to zero, keeping none.
+	fn history_depth() -> T::BlockNumber {
+		Zero::zero()
+	}
+
+	/// How far below `MinimumPeriod` after the previous timestamp a new timestamp may be and still
+	/// be accepted. The timestamp can never go backwards, whatever the tolerance. Defaults to
+	/// zero, strictly enforcing `MinimumPeriod`.
+	fn past_tolerance() -> T::Moment {
+		Zero::zero()
+	}
+
+	/// The number of blocks after genesis during which timestamps only need to be non-decreasing
+	/// rather than increment by `MinimumPeriod`, while the clocks of the first block authors may
+	/// still be poorly aligned. Defaults to zero, enforcing `MinimumPeriod` from the first block.
+	fn grace_period_blocks() -> T::BlockNumber {
+		Zero::zero()
+	}
+
+	/// The number of recent block intervals to average over in `average_block_time`. Defaults to
+	/// zero, keeping none.
+	fn recent_intervals_window() -> u32 {
+		0
+	}
+
+	/// Whether every block must set the timestamp. Test and experimental chains producing blocks
+	/// without a timestamp can disable this, leaving `Now` unchanged in such blocks. Defaults to
+	/// `true`.
+	fn require_timestamp_every_block() -> bool {
+		true
+	}
+
+	/// Find the author of a block from its pre-runtime `digests`, recorded as the one who set its
+	/// timestamp. Defaults to no author.
+	fn find_author<'a, I>(_digests: I) -> Option<T::AccountId>
+		where I: 'a + IntoIterator<Item=(ConsensusEngineId, &'a [u8])>
+	{
+		None
+	}
+
+	/// Whether every timestamp must be strictly greater than the previous one, even if
+	/// `MinimumPeriod` and `past_tolerance` allow equal consecutive timestamps. Defaults to
+	/// `false`.
+	fn strict_monotonic() -> bool {
+		false
+	}
+
+	/// The number of consecutive blocks by the same author whose timestamp is more than
+	/// `max_timestamp_drift` away from the nearest multiple of `block_period()` since genesis
+	/// after which the author is reported to `on_drift_offence`. Defaults to zero, never
+	/// reporting.
+	fn drift_offence_threshold() -> u32 {
+		0
+	}
+
+	/// Called when `offender` set `violations` consecutive drifting timestamps, e.g. to report it
+	/// offline.
+	fn on_drift_offence(_offender: &T::AccountId, _violations: u32) {}
+
+	/// The largest advised period between blocks that can be configured, so a mistake can't make
+	/// block authors target timestamps far in the future. Defaults to zero, for no bound.
+	fn max_block_period() -> T::Moment {
+		Zero::zero()
+	}
+
+	/// How many times longer than `block_period()` the interval between two blocks must be for
+	/// the `SlowBlock` event to be deposited, signalling that the chain fell behind. Defaults to
+	/// zero, never depositing it.
+	fn slow_block_factor() -> u32 {
+		0
+	}
+
+	/// The slot duration of the chain's slot-based consensus, which the genesis block period must
+	/// divide so blocks don't drift away from slot boundaries. Defaults to zero, for no slot
+	/// duration to check against.
+	fn slot_duration() -> T::Moment {
+		Zero::zero()
+	}
+
+	/// Whether block authors should target the average of the recent block intervals, rather than
+	/// `block_period()`, when it is longer, so the proposed timestamp keeps pace with a chain
+	/// running slow. Defaults to `false`.
+	fn adaptive_inherent() -> bool {
+		false
+	}
+
+	/// What to set instead of the timestamp `now` that doesn't increment by at least
+	/// `MinimumPeriod` on the previous one, given the earliest valid timestamp `minimum`, or
+	/// `None` to reject it, aborting the import of the block. Defaults to `None`; clamping to
+	/// `Some(minimum)` is only meant for test chains and sidechains.
+	fn on_invalid_timestamp(_now: T::Moment, _minimum: T::Moment) -> Option<T::Moment> {
+		None
+	}
+}
+
+impl<T: Trait> Settings<T> for () {}
+
 /// The module configuration trait
 pub trait Trait: system::Trait {
+	/// The overarching event type.
+	type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
 	/// Type used for expressing timestamp.
 	type Moment: Parameter + Default + SimpleArithmetic
 		+ Scale<Self::BlockNumber, Output = Self::Moment>;
@@ -214,11 +896,18 @@ pub trait Trait: system::Trait {
 	/// Something which can be notified when the timestamp is set. Set this to `()` if not needed.
 	type OnTimestampSet: OnTimestampSet<Self::Moment>;
 
+	/// The unit of `Moment`, as the number of milliseconds in one moment: `Seconds` or
+	/// `Milliseconds`.
+	type MomentUnit: Get<u64>;
+
 	/// The minimum period between blocks. Beware that this is different to the *expected* period
 	/// that the block production apparatus provides. Your chosen consensus system will generally
 	/// work with this to determine a sensible block time. e.g. For Aura, it will be double this
 	/// period on default settings.
 	type MinimumPeriod: Get<Self::Moment>;
+
+	/// The optional settings of the module. Set this to `()` to keep the default of each.
+	type Settings: Settings<Self>;
 }
 
 decl_module! {
@@ -229,6 +918,12 @@ decl_module! {
 		/// period on default settings.
 		const MinimumPeriod: T::Moment = T::MinimumPeriod::get();
 
+		/// The maximum amount, in the unit of the inherent data, by which a block's timestamp may
+		/// run ahead of the local clock of the node checking it before the block is rejected.
+		const MaxTimestampDrift: u64 = T::Settings::max_timestamp_drift();
+
+		fn deposit_event<T>() = default;
+
 		/// Set the current time.
 		///
 		/// This call should be invoked exactly once per block. It will panic at the finalization
@@ -238,33 +933,267 @@ decl_module! {
 		/// `MinimumPeriod`.
 		///
 		/// The dispatch origin for this call must be `Inherent`.
-		#[weight = SimpleDispatchInfo::FixedOperational(10_000)]
+		#[weight = SimpleDispatchInfo::FixedOperational(SET_WEIGHT)]
 		fn set(origin, #[compact] now: T::Moment) {
 			ensure_none(origin)?;
-			assert!(!<Self as Store>::DidUpdate::exists(), "Timestamp must be updated only once in the block");
-			assert!(
-				Self::now().is_zero() || now >= Self::now() + T::MinimumPeriod::get(),
-				"Timestamp must increment by at least <MinimumPeriod> between sequential blocks"
+			if let Err(e) = Self::try_set(now) {
+				panic!("{}", e.as_str());
+			}
+		}
+
+		/// Set the current time like `set`, also recording the consensus slot `slot` of the block
+		/// in `CurrentSlot`.
+		///
+		/// The dispatch origin for this call must be `Inherent`.
+		#[weight = SimpleDispatchInfo::FixedOperational(SET_WEIGHT)]
+		fn set_with_context(origin, #[compact] now: T::Moment, #[compact] slot: u64) {
+			ensure_none(origin)?;
+			if let Err(e) = Self::try_set(now) {
+				panic!("{}", e.as_str());
+			}
+			<Self as Store>::CurrentSlot::put(slot);
+		}
+
+		/// Set the advised period between blocks.
+		///
+		/// This takes effect from the timestamp inherent of the next block. It does not change the
+		/// `MinimumPeriod` that `set` enforces.
+		///
+		/// The dispatch origin for this call must be `Root`.
+		#[weight = SimpleDispatchInfo::FixedOperational(10_000)]
+		fn set_block_period(origin, #[compact] new: T::Moment) {
+			ensure_root(origin)?;
+			Self::ensure_valid_block_period(&new)?;
+			Self::change_block_period(<system::Module<T>>::block_number(), new);
+		}
+
+		/// Schedule a change of the advised period between blocks, taking effect at the start of
+		/// block `at`. This replaces any change already scheduled for that block.
+		///
+		/// The dispatch origin for this call must be `Root`.
+		#[weight = SimpleDispatchInfo::FixedOperational(10_000)]
+		fn schedule_block_period_change(origin, #[compact] at: T::BlockNumber, #[compact] new: T::Moment) {
+			ensure_root(origin)?;
+			Self::ensure_valid_block_period(&new)?;
+			ensure!(
+				at > <system::Module<T>>::block_number(),
+				"Block period change must be scheduled for a future block"
 			);
-			<Self as Store>::Now::put(now.clone());
-			<Self as Store>::DidUpdate::put(true);
+			<Self as Store>::PendingBlockPeriods::insert(&at, new);
+			<Self as Store>::PendingBlockPeriodBlocks::mutate(|blocks| if !blocks.contains(&at) {
+				blocks.push(at);
+			});
+		}
 
-			<T::OnTimestampSet as OnTimestampSet<_>>::on_timestamp_set(now);
+		/// Clear the flag recording that the timestamp was set in this block, so it can be set
+		/// again. A break-glass tool to recover from the flag being stuck.
+		///
+		/// The dispatch origin for this call must be `Root`.
+		#[weight = SimpleDispatchInfo::FixedOperational(10_000)]
+		fn force_clear_did_update(origin) {
+			ensure_root(origin)?;
+			<Self as Store>::DidUpdate::kill();
 		}
 
-		fn on_finalize() {
-			assert!(<Self as Store>::DidUpdate::take(), "Timestamp must be updated once in the block");
+		fn on_initialize(n: T::BlockNumber) {
+			// `on_finalize` normally clears this already; make sure a flag left over from a block
+			// that didn't finalize can't block this one from setting its timestamp.
+			<Self as Store>::DidUpdate::kill();
+			<Self as Store>::SetPosition::kill();
+			<Self as Store>::CurrentSlot::kill();
+			Self::prune_historical_timestamps(n);
+
+			if let Some(period) = <Self as Store>::PendingBlockPeriods::take(n) {
+				<Self as Store>::PendingBlockPeriodBlocks::mutate(|blocks| blocks.retain(|b| *b != n));
+				// the timestamp of this block is the first one produced at the new period.
+				Self::change_block_period(n.saturating_sub(One::one()), period);
+			}
+		}
+
+		fn on_finalize(n: T::BlockNumber) {
+			let updated = <Self as Store>::DidUpdate::take();
+			assert!(
+				updated || !T::Settings::require_timestamp_every_block(),
+				"Timestamp must be updated once in the block",
+			);
+			if !updated {
+				Self::deposit_event(RawEvent::TimestampMissing(n));
+			}
 		}
 	}
 }
 
+decl_event!(
+	pub enum Event<T> where <T as Trait>::Moment, <T as system::Trait>::BlockNumber {
+		/// The timestamp was set for this block; the new and the previous timestamp are supplied.
+		TimestampSet(Moment, Moment),
+		/// The block didn't set the timestamp, as allowed when
+		/// `Settings::require_timestamp_every_block` is disabled.
+		TimestampMissing(BlockNumber),
+		/// The timestamp was set by an extrinsic at an index other than
+		/// `Settings::expected_set_position`; the expected and the actual index are supplied.
+		UnexpectedSetPosition(u32, u32),
+		/// The interval since the previous block exceeded `block_period()` by more than
+		/// `Settings::slow_block_factor` times; the interval is supplied.
+		SlowBlock(Moment),
+	}
+);
+
 decl_storage! {
 	trait Store for Module<T: Trait> as Timestamp {
-		/// Current time for the current block.
-		pub Now get(now) build(|_| 0.into()): T::Moment;
+		/// Current time for the current block. Starts at `genesis_timestamp`, so the first block
+		/// must be at least `MinimumPeriod` after that, unless it is zero.
+		pub Now get(now) config(genesis_timestamp): T::Moment;
+
+		/// Time of the previous block, i.e. the value of `Now` before it was last updated.
+		pub PreviousNow get(previous_now): T::Moment;
+
+		/// The period between blocks that block authors are advised to produce at. Unlike
+		/// `MinimumPeriod` this is not enforced by `set`; it only raises the timestamp proposed by
+		/// the inherent. Use `block_period()` to read the effective value.
+		BlockPeriod config(period) build(|config: &GenesisConfig<T>| {
+			assert!(!config.period.is_zero(), "Timestamp block period must be non-zero");
+			let max = T::Settings::max_block_period();
+			assert!(
+				max.is_zero() || config.period <= max,
+				"Timestamp block period must not exceed Settings::max_block_period"
+			);
+			let slot_duration = T::Settings::slot_duration();
+			if !slot_duration.is_zero() {
+				assert!(
+					(slot_duration.clone() % config.period.clone()).is_zero(),
+					"Timestamp block period must divide the slot duration"
+				);
+				if slot_duration != config.period {
+					log::warn!(
+						target: "timestamp",
+						"Timestamp block period {:?} is shorter than the slot duration {:?}",
+						config.period,
+						slot_duration,
+					);
+				}
+			}
+			config.period.clone()
+		}): T::Moment;
+
+		/// Changes of `BlockPeriod` scheduled with `schedule_block_period_change`, keyed by the block
+		/// they take effect at.
+		pub PendingBlockPeriods get(pending_block_period): map T::BlockNumber => Option<T::Moment>;
+
+		/// The blocks with a change in `PendingBlockPeriods`, so the changes can be enumerated.
+		pub PendingBlockPeriodBlocks get(pending_block_period_blocks): Vec<T::BlockNumber>;
+
+		/// The block number and expected timestamp of the last block produced before the latest
+		/// change of `BlockPeriod`, from which `expected_timestamp` counts. Until the period
+		/// changes, it counts from the genesis timestamp instead.
+		pub ScheduleAnchor get(schedule_anchor): Option<(T::BlockNumber, T::Moment)>;
+
+		/// How far the most recently set timestamps were past the start of their slot, i.e. their
+		/// offset from the last multiple of `block_period()` since genesis, oldest first, bounded
+		/// by `Settings::adaptive_drift_window`.
+		pub RecentDrifts get(recent_drifts): Vec<u64>;
+
+		/// The most recently set timestamps, oldest first, bounded by
+		/// `Settings::reported_timestamps_window`.
+		pub ReportedTimestamps get(reported_timestamps): Vec<T::Moment>;
+
+		/// The timestamps of the last `Settings::history_depth` blocks, keyed by block number.
+		pub HistoricalTimestamps get(historical_timestamp): map T::BlockNumber => Option<T::Moment>;
+
+		/// The most recent intervals between block timestamps, oldest first, bounded by
+		/// `Settings::recent_intervals_window`.
+		pub RecentIntervals get(recent_intervals): Vec<T::Moment>;
+
+		/// The timestamp at genesis, i.e. `genesis_timestamp` of the genesis config.
+		pub GenesisTimestamp get(genesis_timestamp) build(|config: &GenesisConfig<T>| {
+			config.genesis_timestamp.clone()
+		}): T::Moment;
+
+		/// The largest lag of block production behind schedule observed by `LagRecorder`, i.e. how
+		/// far a timestamp was past the one expected at its block.
+		pub MaxLag get(max_lag): T::Moment;
+
+		/// The author of the block that last set the timestamp, if it is known.
+		pub LastSetter get(last_setter): Option<T::AccountId>;
+
+		/// The number of consecutive drifting timestamps set by each block author, as counted for
+		/// `Settings::drift_offence_threshold`.
+		pub DriftViolations get(drift_violations): map T::AccountId => u32;
+
+		/// The block authors with a non-zero count in `DriftViolations`, so it can be cleared.
+		pub DriftingAuthors get(drifting_authors): Vec<T::AccountId>;
+
+		/// The number of times the timestamp was set, i.e. the number of blocks with a timestamp.
+		pub UpdateCount get(update_count): u64;
+
+		/// The index of the extrinsic that set the timestamp in this block, if it was set by one.
+		pub SetPosition get(set_position): Option<u32>;
+
+		/// The consensus slot of this block, if the timestamp was set with `set_with_context`.
+		pub CurrentSlot get(current_slot): Option<u64>;
 
 		/// Did the timestamp get updated in this block?
-		DidUpdate: bool;
+		DidUpdate get(is_updated): bool;
+	}
+}
+
+#[cfg(feature = "std")]
+impl<T: Trait> GenesisConfig<T> {
+	/// A genesis advising a period of `period` between blocks, starting at a zero timestamp.
+	pub fn with_block_period(period: T::Moment) -> Self {
+		GenesisConfig { period, genesis_timestamp: Zero::zero() }
+	}
+
+	/// A genesis advising three seconds between blocks, given the `MomentUnit` of the chain.
+	pub fn with_three_second_blocks() -> Self {
+		Self::with_block_period(<Module<T>>::from_unix_secs(3))
+	}
+
+	/// A genesis advising six seconds between blocks, given the `MomentUnit` of the chain.
+	pub fn with_six_second_blocks() -> Self {
+		Self::with_block_period(<Module<T>>::from_unix_secs(6))
+	}
+}
+
+/// The error returned when the timestamp is too close to the maximum `Moment` to advance.
+const OVERFLOW_ERROR: &'static str = "Timestamp overflowed when adding the minimum period";
+
+/// Errors of the fallible paths setting the timestamp or the block period outside of the
+/// inherent, e.g. `try_set`. They convert into the `&'static str` errors of dispatchable calls.
+///
+/// The `set` inherent still panics on any of these, to abort the import of an invalid block.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Error {
+	/// The timestamp was already set in this block.
+	AlreadySet,
+	/// The timestamp doesn't increment by at least `MinimumPeriod` on the previous one.
+	InsufficientIncrement,
+	/// The current timestamp is too close to the maximum `Moment` to advance by `MinimumPeriod`.
+	Overflow,
+	/// The block period is zero.
+	PeriodZero,
+	/// The block period exceeds `Settings::max_block_period`.
+	PeriodTooLarge,
+}
+
+impl Error {
+	/// A description of the error.
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Error::AlreadySet => "Timestamp must be updated only once in the block",
+			Error::InsufficientIncrement =>
+				"Timestamp must increment by at least <MinimumPeriod> between sequential blocks",
+			Error::Overflow => OVERFLOW_ERROR,
+			Error::PeriodZero => "Block period must be non-zero",
+			Error::PeriodTooLarge => "Block period must not exceed Settings::max_block_period",
+		}
+	}
+}
+
+impl From<Error> for &'static str {
+	fn from(error: Error) -> &'static str {
+		error.as_str()
 	}
 }
 
@@ -277,11 +1206,727 @@ impl<T: Trait> Module<T> {
 		Self::now()
 	}
 
-	/// Set the timestamp to something in particular. Only used for tests.
+	/// Get the current time for the current block, or `None` if it hasn't been set yet in this
+	/// block.
+	pub fn try_now() -> Option<T::Moment> {
+		if Self::is_updated() {
+			Some(Self::now())
+		} else {
+			None
+		}
+	}
+
+	/// Get the timestamp confirmed for the current block, for code running after the `set`
+	/// inherent, e.g. in `on_finalize`.
+	///
+	/// Unlike `now()`, which silently returns the previous block's timestamp until `set` is
+	/// called, this panics if the timestamp hasn't been set yet in this block.
+	pub fn now_finalized() -> T::Moment {
+		Self::try_now().expect("Timestamp must be set before reading the finalized timestamp")
+	}
+
+	/// Check whether `now` could be set as the current time, like `try_set` but without setting it
+	/// and reporting why it can't as an `InherentError`.
+	pub fn check_set(now: T::Moment) -> result::Result<(), InherentError> {
+		Self::settable_timestamp(now).map(|_| ()).map_err(|e| match e {
+			Error::AlreadySet => InherentError::AlreadySet,
+			Error::InsufficientIncrement =>
+				InherentError::ValidAtTimestamp(Self::minimum_valid_timestamp().saturated_into()),
+			e => InherentError::Other(e.as_str().into()),
+		})
+	}
+
+	/// The timestamp to store when setting `now` as the current time, i.e. `now` itself or what
+	/// `Settings::on_invalid_timestamp` clamps it to, or why it can't be set.
+	fn settable_timestamp(now: T::Moment) -> result::Result<T::Moment, Error> {
+		ensure!(!<Self as Store>::DidUpdate::exists(), Error::AlreadySet);
+		if Self::now().is_zero() {
+			return Ok(now);
+		}
+
+		let earliest = Self::checked_minimum_valid_timestamp().ok_or(Error::Overflow)?;
+		if now < earliest {
+			T::Settings::on_invalid_timestamp(now, earliest).ok_or(Error::InsufficientIncrement)
+		} else {
+			Ok(now)
+		}
+	}
+
+	/// Set the current time, enforcing the same rules as the `set` inherent.
+	///
+	/// Unlike the inherent, which panics to abort the import of an invalid block, this returns an
+	/// error and leaves storage untouched if the timestamp was already set in this block or does
+	/// not increment by at least `MinimumPeriod`, unless `Settings::on_invalid_timestamp` clamps
+	/// it.
+	pub fn try_set(now: T::Moment) -> result::Result<(), Error> {
+		let now = Self::settable_timestamp(now)?;
+
+		let previous = Self::now();
+		T::Settings::on_before_timestamp_set(
+			previous.clone(),
+			now.clone(),
+		);
+		<Self as Store>::PreviousNow::put(previous.clone());
+		<Self as Store>::Now::put(now.clone());
+		<Self as Store>::DidUpdate::put(true);
+		<Self as Store>::UpdateCount::mutate(|count| *count = count.saturating_add(1));
+		Self::note_setter();
+		Self::note_set_position();
+		Self::note_drift(now.clone());
+		Self::note_drift_sample(now.clone());
+		Self::note_reported_timestamp(now.clone());
+		Self::note_historical_timestamp(now.clone());
+		Self::note_interval();
+
+		Self::deposit_event(RawEvent::TimestampSet(now.clone(), previous));
+		Self::note_slow_block();
+
+		<T::OnTimestampSet as OnTimestampSet<_>>::on_timestamp_set(now.clone());
+		if let Err(_e) = T::Settings::try_on_timestamp_set(now.clone()) {
+			#[cfg(feature = "std")]
+			log::warn!(target: "timestamp", "A hook failed after setting the timestamp to {:?}: {}", now, _e);
+		}
+		T::Settings::on_timestamp_set_with_context(
+			now,
+			<system::Module<T>>::block_number(),
+		);
+		Ok(())
+	}
+
+	/// The advised period between blocks, which block authors target when creating the timestamp
+	/// inherent.
+	///
+	/// This is never less than `MinimumPeriod`, so it falls back to that when no advised period
+	/// was configured.
+	pub fn block_period() -> T::Moment {
+		cmp::max(<Self as Store>::BlockPeriod::get(), T::MinimumPeriod::get())
+	}
+
+	/// The minimum period between blocks, enforced when the timestamp is set.
+	pub fn minimum_period() -> T::Moment {
+		T::MinimumPeriod::get()
+	}
+
+	/// The time that passed between the previous block and the current one.
+	///
+	/// This is zero when there is no previous timestamp, i.e. for the first block that sets one.
+	pub fn elapsed() -> T::Moment {
+		let previous = Self::previous_now();
+		if previous.is_zero() {
+			Zero::zero()
+		} else {
+			Self::now().saturating_sub(previous)
+		}
+	}
+
+	/// A seed mixing the current timestamp and block number, hashed with `T::Hashing`, e.g. as
+	/// weak randomness.
+	///
+	/// NOTE: the block author chooses the timestamp within a small range, so this has very little
+	/// entropy and must never be used where randomness matters for security.
+	pub fn timestamp_seed() -> T::Hash {
+		(Self::now(), <system::Module<T>>::block_number()).using_encoded(T::Hashing::hash)
+	}
+
+	/// The storage key of `Now`, e.g. to read the timestamp of a block from a state proof.
+	///
+	/// The value under this key is the plain SCALE encoding of `Moment`, as for any storage value;
+	/// only the argument of the `set` call is compact encoded.
+	pub fn now_storage_key() -> Vec<u8> {
+		Twox128::hash(<Self as Store>::Now::key()).to_vec()
+	}
+
+	/// The time that passed since the genesis timestamp, e.g. for the total age of the chain.
+	pub fn since_genesis() -> T::Moment {
+		Self::now().saturating_sub(Self::genesis_timestamp())
+	}
+
+	/// The timestamp block `block` would have if every block since genesis was produced exactly
+	/// the advised period after its parent, e.g. to predict or verify the timestamp of a block.
+	/// This saturates at the maximum `Moment` rather than overflowing.
+	///
+	/// Changes of the advised period only apply to the blocks after them, so the expected
+	/// timestamp never jumps ahead of the timestamps honest block authors produce. Blocks before
+	/// the latest change are extrapolated backwards at the current period.
+	pub fn expected_timestamp(block: T::BlockNumber) -> T::Moment {
+		let (anchor_block, anchor) = Self::schedule_origin();
+		let period = Self::block_period();
+		if block >= anchor_block {
+			anchor.saturating_add(Self::scale_by_blocks(period, block - anchor_block))
+		} else {
+			anchor.saturating_sub(Self::scale_by_blocks(period, anchor_block - block))
+		}
+	}
+
+	/// The block and timestamp `expected_timestamp` counts from at the current block period.
+	fn schedule_origin() -> (T::BlockNumber, T::Moment) {
+		Self::schedule_anchor().unwrap_or_else(|| (Zero::zero(), Self::genesis_timestamp()))
+	}
+
+	/// Make `period` the advised period between blocks from the block after `last_block`, so the
+	/// expected timestamps up to `last_block` stay as they are.
+	fn change_block_period(last_block: T::BlockNumber, period: T::Moment) {
+		let anchor = Self::expected_timestamp(last_block.clone());
+		<Self as Store>::ScheduleAnchor::put((last_block, anchor));
+		<Self as Store>::BlockPeriod::put(period);
+	}
+
+	/// The current timestamp rounded up to the next slot boundary, i.e. multiple of
+	/// `block_period()` since the genesis timestamp counted across changes of the period like
+	/// `expected_timestamp`, e.g. to schedule something on a period boundary. A timestamp already on a
+	/// boundary is returned unchanged, and the result saturates at the maximum `Moment`.
+	pub fn now_ceil_to_period() -> T::Moment {
+		let now = Self::now();
+		let period = Self::block_period();
+		if period.is_zero() {
+			return now;
+		}
+
+		let origin = Self::schedule_origin().1;
+		let remainder = Self::slot_offset(now.clone(), period.clone());
+		if remainder.is_zero() {
+			cmp::max(now, origin)
+		} else {
+			now.saturating_add(period - remainder)
+		}
+	}
+
+	/// The earliest timestamp whose slot, counted in block periods since the genesis timestamp and
+	/// across changes of the period, is not before the slot of the current block.
+	fn earliest_slot_timestamp() -> u64 {
+		Self::expected_timestamp(<system::Module<T>>::block_number()).saturated_into::<u64>()
+	}
+
+	/// `moment` multiplied by the number of blocks `blocks`, e.g. the duration of `blocks` block
+	/// periods. This saturates at the maximum `Moment` rather than overflowing.
+	pub fn scale_by_blocks(moment: T::Moment, blocks: T::BlockNumber) -> T::Moment {
+		moment.saturating_mul(T::Moment::saturated_from(blocks.saturated_into::<u128>()))
+	}
+
+	/// `moment` multiplied by the number of blocks `blocks`, or `None` if the result overflows
+	/// `Moment`.
+	pub fn checked_scale_by_blocks(moment: T::Moment, blocks: T::BlockNumber) -> Option<T::Moment> {
+		let blocks = blocks.saturated_into::<u128>();
+		if blocks > T::Moment::max_value().saturated_into::<u128>() {
+			// the block count itself does not fit, so only a zero moment can be scaled.
+			return if moment.is_zero() { Some(Zero::zero()) } else { None };
+		}
+		moment.checked_mul(&T::Moment::saturated_from(blocks))
+	}
+
+	/// The time from `start` to `end`, or zero if `end` is before `start`.
+	pub fn duration_between(start: T::Moment, end: T::Moment) -> T::Moment {
+		end.saturating_sub(start)
+	}
+
+	/// The current timestamp plus `delta`, e.g. for a deadline. This saturates at the maximum
+	/// `Moment` rather than overflowing.
+	pub fn now_plus(delta: T::Moment) -> T::Moment {
+		Self::now().saturating_add(delta)
+	}
+
+	/// Whether `deadline` has been reached, i.e. the current timestamp is at or after it.
+	pub fn is_expired(deadline: T::Moment) -> bool {
+		Self::now() >= deadline
+	}
+
+	/// The time remaining until `deadline`, or zero if it has been reached.
+	pub fn time_until(deadline: T::Moment) -> T::Moment {
+		deadline.saturating_sub(Self::now())
+	}
+
+	/// The number of whole block periods until `target`, i.e. roughly how many blocks are left
+	/// until it at the advised cadence, or zero if it has been reached. This saturates at the
+	/// maximum block number, which it also is if the block period is zero.
+	pub fn blocks_until(target: T::Moment) -> T::BlockNumber {
+		let remaining = Self::time_until(target);
+		if remaining.is_zero() {
+			return Zero::zero();
+		}
+		remaining.checked_div(&Self::block_period())
+			.map_or_else(T::BlockNumber::max_value, |blocks| blocks.saturated_into::<u128>().saturated_into())
+	}
+
+	/// The mean of the intervals kept in `RecentIntervals`, i.e. the observed average block time
+	/// over the last `Settings::recent_intervals_window` blocks. Zero if no interval was recorded
+	/// yet.
+	pub fn average_block_time() -> T::Moment {
+		let intervals = Self::recent_intervals();
+		if intervals.is_empty() {
+			return Zero::zero();
+		}
+
+		let count = T::Moment::from(intervals.len() as u32);
+		let total = intervals.into_iter().fold(T::Moment::zero(), |acc, i| acc.saturating_add(i));
+		total / count
+	}
+
+	/// The median of the recently set timestamps kept in `ReportedTimestamps`, or `None` if there
+	/// are none. For an even number of samples this is the mean of the two middle ones.
+	///
+	/// This is less sensitive to a single block author with a bad clock than `now()`.
+	pub fn median_timestamp() -> Option<T::Moment> {
+		let mut samples = Self::reported_timestamps();
+		samples.sort();
+
+		let len = samples.len();
+		if len == 0 {
+			None
+		} else if len % 2 == 0 {
+			let (a, b) = (samples[len / 2 - 1].clone(), samples[len / 2].clone());
+			let two = T::Moment::one() + T::Moment::one();
+			// `a <= b`, so this can't overflow.
+			Some(a.clone() + (b - a) / two)
+		} else {
+			Some(samples[len / 2].clone())
+		}
+	}
+
+	/// Ensure `period` can be used as the advised period between blocks.
+	fn ensure_valid_block_period(period: &T::Moment) -> result::Result<(), Error> {
+		ensure!(!period.is_zero(), Error::PeriodZero);
+		let max = T::Settings::max_block_period();
+		ensure!(max.is_zero() || *period <= max, Error::PeriodTooLarge);
+		Ok(())
+	}
+
+	/// The smallest timestamp the next block may have, i.e. the current timestamp plus
+	/// `MinimumPeriod`, less `Settings::past_tolerance`.
+	///
+	/// This saturates at the maximum `Moment`, although `set` rejects any timestamp once adding
+	/// `MinimumPeriod` to the current one overflows.
+	pub fn minimum_valid_timestamp() -> T::Moment {
+		Self::checked_minimum_valid_timestamp().unwrap_or_else(T::Moment::max_value)
+	}
+
+	/// How far the timestamp `t` runs ahead of the local clock `data`, if that is more than half of
+	/// `max_future_drift()`. Only used to warn about failing clocks.
+	#[cfg(feature = "std")]
+	fn significant_drift(t: u64, data: u64) -> Option<u64> {
+		let drift = t.saturating_sub(data);
+		if drift > Self::max_future_drift() / 2 {
+			Some(drift)
+		} else {
+			None
+		}
+	}
+
+	/// The earliest timestamp the next block may have, or `None` if adding the minimum period to
+	/// the current timestamp overflows.
+	fn checked_minimum_valid_timestamp() -> Option<T::Moment> {
+		let now = Self::now();
+		let floor = if T::Settings::strict_monotonic() { now.checked_add(&One::one())? } else { now };
+		if Self::in_grace_period() {
+			return Some(floor);
+		}
+		let minimum = now.checked_add(&T::MinimumPeriod::get())?;
+		Some(cmp::max(minimum.saturating_sub(T::Settings::past_tolerance()), floor))
+	}
+
+	/// Whether the current block is one of the first `Settings::grace_period_blocks` blocks after
+	/// genesis.
+	fn in_grace_period() -> bool {
+		let grace = T::Settings::grace_period_blocks();
+		!grace.is_zero() && <system::Module<T>>::block_number() <= grace
+	}
+
+	fn note_setter() {
+		let digest = <system::Module<T>>::digest();
+		let pre_runtime_digests = digest.logs.iter().filter_map(|d| d.as_pre_runtime());
+		match T::Settings::find_author(pre_runtime_digests) {
+			Some(author) => <Self as Store>::LastSetter::put(author),
+			None => <Self as Store>::LastSetter::kill(),
+		}
+	}
+
+	fn note_set_position() {
+		let position = <system::Module<T>>::extrinsic_index();
+		match position {
+			Some(position) => <Self as Store>::SetPosition::put(position),
+			None => <Self as Store>::SetPosition::kill(),
+		}
+		match (T::Settings::expected_set_position(), position) {
+			(Some(expected), Some(position)) if position != expected =>
+				Self::deposit_event(RawEvent::UnexpectedSetPosition(expected, position)),
+			_ => {},
+		}
+	}
+
+	fn note_drift(now: T::Moment) {
+		let threshold = T::Settings::drift_offence_threshold();
+		let author = match Self::last_setter() {
+			Some(author) if !threshold.is_zero() => author,
+			_ => return,
+		};
+
+		if !Self::is_off_slot(now) {
+			Self::forget_drift_violations(&author);
+			return;
+		}
+
+		let violations = Self::drift_violations(&author).saturating_add(1);
+		if violations >= threshold {
+			Self::forget_drift_violations(&author);
+			T::Settings::on_drift_offence(&author, violations);
+		} else {
+			if violations == 1 {
+				<Self as Store>::DriftingAuthors::mutate(|authors| authors.push(author.clone()));
+			}
+			<Self as Store>::DriftViolations::insert(&author, violations);
+		}
+	}
+
+	fn forget_drift_violations(author: &T::AccountId) {
+		if !<Self as Store>::DriftViolations::take(author).is_zero() {
+			<Self as Store>::DriftingAuthors::mutate(|authors| authors.retain(|a| a != author));
+		}
+	}
+
+	/// Whether `now` is more than `Settings::max_timestamp_drift` away from the nearest slot
+	/// boundary, i.e. a multiple of `block_period()` since the genesis timestamp, counted across
+	/// changes of the period like `expected_timestamp`.
+	fn is_off_slot(now: T::Moment) -> bool {
+		let period = Self::block_period();
+		if period.is_zero() {
+			return false;
+		}
+
+		let offset = Self::slot_offset(now, period.clone());
+		let drift = cmp::min(offset.clone(), period - offset);
+		drift.saturated_into::<u64>() > T::Settings::max_timestamp_drift()
+	}
+
+	fn note_drift_sample(now: T::Moment) {
+		let window = T::Settings::adaptive_drift_window() as usize;
+		let period = Self::block_period();
+		if T::Settings::max_adaptive_drift().is_zero() || window == 0 || period.is_zero() {
+			return;
+		}
+
+		let drift = Self::slot_offset(now, period).saturated_into::<u64>();
+		let mut samples = Self::recent_drifts();
+		// take into account the item we haven't pushed yet.
+		let to_prune = (samples.len() + 1).saturating_sub(window);
+		samples.drain(..to_prune);
+		samples.push(drift);
+		<Self as Store>::RecentDrifts::put(samples);
+	}
+
+	/// How far `now` is past the start of its slot of length `period`, which must be non-zero.
+	fn slot_offset(now: T::Moment, period: T::Moment) -> T::Moment {
+		now.saturating_sub(Self::schedule_origin().1) % period
+	}
+
+	/// The future drift accepted by `check_inherent`: `Settings::max_timestamp_drift` widened by
+	/// the average of `RecentDrifts`, up to `Settings::max_adaptive_drift`.
+	pub fn max_future_drift() -> u64 {
+		let samples = Self::recent_drifts();
+		let widening = if samples.is_empty() {
+			0
+		} else {
+			let total = samples.iter().fold(0u128, |total, d| total + u128::from(*d));
+			(total / samples.len() as u128) as u64
+		};
+		T::Settings::max_timestamp_drift().saturating_add(cmp::min(widening, T::Settings::max_adaptive_drift()))
+	}
+
+	fn note_reported_timestamp(now: T::Moment) {
+		let window = T::Settings::reported_timestamps_window() as usize;
+		if window == 0 {
+			return;
+		}
+
+		let mut samples = Self::reported_timestamps();
+		// take into account the item we haven't pushed yet.
+		let to_prune = (samples.len() + 1).saturating_sub(window);
+		samples.drain(..to_prune);
+		samples.push(now);
+		<Self as Store>::ReportedTimestamps::put(samples);
+	}
+
+	fn note_interval() {
+		let window = T::Settings::recent_intervals_window() as usize;
+		if window == 0 || Self::previous_now().is_zero() {
+			return;
+		}
+
+		let mut intervals = Self::recent_intervals();
+		let to_prune = (intervals.len() + 1).saturating_sub(window);
+		intervals.drain(..to_prune);
+		intervals.push(Self::elapsed());
+		<Self as Store>::RecentIntervals::put(intervals);
+	}
+
+	fn note_slow_block() {
+		let factor = T::Settings::slow_block_factor();
+		if factor == 0 || Self::previous_now().is_zero() {
+			return;
+		}
+
+		let interval = Self::elapsed();
+		if interval > Self::block_period().saturating_mul(T::Moment::from(factor)) {
+			Self::deposit_event(RawEvent::SlowBlock(interval));
+		}
+	}
+
+	/// The timestamps in `HistoricalTimestamps` of the blocks `from` to `to`, inclusive, in block
+	/// order, skipping blocks without one.
+	///
+	/// The range ends at the current block at the latest, and at most its last
+	/// `Settings::history_depth` blocks are read, as no older timestamps are kept.
+	pub fn historical_timestamps_range(
+		from: T::BlockNumber,
+		to: T::BlockNumber,
+	) -> Vec<(T::BlockNumber, T::Moment)> {
+		let depth = T::Settings::history_depth();
+		let to = cmp::min(to, <system::Module<T>>::block_number());
+		if from > to || depth.is_zero() {
+			return Vec::new();
+		}
+
+		let mut block = cmp::max(from, to.saturating_sub(depth - One::one()));
+		let mut timestamps = Vec::new();
+		loop {
+			if let Some(timestamp) = Self::historical_timestamp(&block) {
+				timestamps.push((block.clone(), timestamp));
+			}
+			if block >= to {
+				return timestamps;
+			}
+			block += One::one();
+		}
+	}
+
+	/// Whether the block `block` set its timestamp, as recorded in `HistoricalTimestamps`.
+	///
+	/// This is only known for the last `Settings::history_depth` blocks, so it is `false` for any
+	/// older block and for every block if `Settings::history_depth` is zero.
+	pub fn was_updated_at(block: T::BlockNumber) -> bool {
+		<Self as Store>::HistoricalTimestamps::exists(block)
+	}
+
+	fn note_historical_timestamp(now: T::Moment) {
+		let depth = T::Settings::history_depth();
+		if depth.is_zero() {
+			return;
+		}
+
+		<Self as Store>::HistoricalTimestamps::insert(<system::Module<T>>::block_number(), now);
+	}
+
+	/// Drop the timestamp of the block falling out of the last `Settings::history_depth` blocks at
+	/// the start of block `n`, whether or not the blocks in between set their timestamp.
+	fn prune_historical_timestamps(n: T::BlockNumber) {
+		let depth = T::Settings::history_depth();
+		if !depth.is_zero() && n >= depth {
+			<Self as Store>::HistoricalTimestamps::remove(n - depth);
+		}
+	}
+
+	/// Get the current time for the current block in milliseconds, given the `MomentUnit` of the
+	/// chain. The result saturates at `u64::max_value()` rather than overflowing.
+	pub fn now_as_millis() -> u64 {
+		Self::now().saturated_into::<u64>().saturating_mul(T::MomentUnit::get())
+	}
+
+	/// The current time for the current block in whole seconds, whatever the `MomentUnit`.
+	pub fn now_as_secs() -> u64 {
+		Self::to_unix_secs(Self::now())
+	}
+
+	/// The earliest time the next block may be proposed at when producing blocks every
+	/// `block_period()`, in whole seconds since the unix epoch, e.g. for authoring schedulers to
+	/// sleep until. This assumes `Moment` counts from the unix epoch.
+	#[cfg(feature = "std")]
+	pub fn next_block_deadline_unix_secs() -> u64 {
+		Self::to_unix_secs(Self::now().saturating_add(Self::block_period()))
+	}
+
+	/// Convert `moment` into whole seconds since the unix epoch, given the `MomentUnit` of the
+	/// chain. This assumes `Moment` counts from the unix epoch and saturates at `u64::max_value()`.
+	pub fn to_unix_secs(moment: T::Moment) -> u64 {
+		let millis = moment.saturated_into::<u128>().saturating_mul(u128::from(T::MomentUnit::get()));
+		(millis / 1000).saturated_into::<u64>()
+	}
+
+	/// Convert `secs` since the unix epoch into a `Moment`, given the `MomentUnit` of the chain.
+	/// This is the inverse of `to_unix_secs` and saturates at the maximum `Moment`.
+	pub fn from_unix_secs(secs: u64) -> T::Moment {
+		(u128::from(secs) * 1000)
+			.checked_div(u128::from(T::MomentUnit::get()))
+			.map_or_else(T::Moment::max_value, |moment| moment.saturated_into())
+	}
+
+	/// The slot duration of slot-based consensus in milliseconds, i.e. `block_period()` given the
+	/// `MomentUnit` of the chain. The result saturates at `u64::max_value()`.
+	pub fn slot_duration() -> u64 {
+		Self::block_period().saturated_into::<u64>().saturating_mul(T::MomentUnit::get())
+	}
+
+	/// The current timestamp as a date and time in whole seconds, given the `MomentUnit` of the
+	/// chain. `None` if the timestamp is out of the range `NaiveDateTime` can represent.
 	#[cfg(feature = "std")]
+	pub fn now_as_datetime() -> Option<chrono::NaiveDateTime> {
+		let secs = Self::to_unix_secs(Self::now());
+		if secs > i64::max_value() as u64 {
+			return None;
+		}
+		chrono::NaiveDateTime::from_timestamp_opt(secs as i64, 0)
+	}
+
+	/// Convert `moment` to a `Duration`, given the `MomentUnit` of the chain. The result
+	/// saturates at the largest duration.
+	#[cfg(feature = "std")]
+	pub fn moment_to_duration(moment: T::Moment) -> std::time::Duration {
+		let millis = moment.saturated_into::<u64>().saturating_mul(T::MomentUnit::get());
+		std::time::Duration::from_millis(millis)
+	}
+
+	/// Convert `duration` to a `Moment`, given the `MomentUnit` of the chain. Any fraction of a
+	/// moment is truncated and the result saturates at the maximum `Moment`.
+	#[cfg(feature = "std")]
+	pub fn duration_to_moment(duration: std::time::Duration) -> T::Moment {
+		let moments = duration.as_millis() / u128::from(cmp::max(T::MomentUnit::get(), 1));
+		T::Moment::saturated_from(moments)
+	}
+
+	/// Set the timestamp to something in particular. Only used for tests and benchmarks.
+	#[cfg(any(feature = "std", feature = "runtime-benchmarks"))]
 	pub fn set_timestamp(now: T::Moment) {
 		<Self as Store>::Now::put(now);
 	}
+
+	/// Multiply every stored moment by `factor`, for a runtime upgrade changing the unit of `Moment`,
+	/// e.g. with a `factor` of 1000 when switching from seconds to milliseconds. Without it, the
+	/// first timestamp set by the upgraded runtime could look like it goes backwards.
+	///
+	/// This must be called once, by the migration code of the upgraded runtime, before any new
+	/// timestamp is set. The moments saturate at their maximum. The entries of
+	/// `HistoricalTimestamps` are left as they are, as they can't be enumerated; they expire after
+	/// `Settings::history_depth` blocks.
+	pub fn migrate_moment_scale(factor: T::Moment) {
+		let scale = |m: T::Moment| m.saturating_mul(factor.clone());
+		let scale_all = |ms: Vec<T::Moment>| ms.into_iter().map(scale).collect::<Vec<_>>();
+		let factor_u64 = factor.clone().saturated_into::<u64>();
+
+		<Self as Store>::Now::mutate(|m| *m = scale(m.clone()));
+		<Self as Store>::PreviousNow::mutate(|m| *m = scale(m.clone()));
+		if <Self as Store>::BlockPeriod::exists() {
+			<Self as Store>::BlockPeriod::mutate(|m| *m = scale(m.clone()));
+		}
+		<Self as Store>::GenesisTimestamp::mutate(|m| *m = scale(m.clone()));
+		if let Some((block, anchor)) = Self::schedule_anchor() {
+			<Self as Store>::ScheduleAnchor::put((block, scale(anchor)));
+		}
+		<Self as Store>::MaxLag::mutate(|m| *m = scale(m.clone()));
+		<Self as Store>::ReportedTimestamps::mutate(|ms| *ms = scale_all(rstd::mem::replace(ms, Vec::new())));
+		<Self as Store>::RecentIntervals::mutate(|ms| *ms = scale_all(rstd::mem::replace(ms, Vec::new())));
+		<Self as Store>::RecentDrifts::mutate(|ds| for d in ds.iter_mut() {
+			*d = d.saturating_mul(factor_u64);
+		});
+		for block in Self::pending_block_period_blocks() {
+			<Self as Store>::PendingBlockPeriods::mutate(&block, |m| *m = m.clone().map(scale));
+		}
+	}
+
+	/// Initialize `PreviousNow` to the current timestamp, for a runtime upgrade of a chain that
+	/// started without it. Otherwise the first `elapsed()` after the upgrade would count from zero
+	/// rather than from the timestamp of the last block.
+	///
+	/// This must be called by the migration code of the upgraded runtime, before any new timestamp
+	/// is set. It does nothing if `PreviousNow` is already stored, so calling it again is harmless.
+	pub fn migrate_previous_now() {
+		if !<Self as Store>::PreviousNow::exists() {
+			<Self as Store>::PreviousNow::put(Self::now());
+		}
+	}
+
+	/// Set the current and the previous timestamp to something in particular, marking the timestamp
+	/// as set in this block if `did_update` is true. Only used for tests and benchmarks.
+	///
+	/// NOTE: this bypasses all checks of `set`, so it can break the invariants consensus relies on,
+	/// e.g. that the timestamp never decreases.
+	#[cfg(any(feature = "std", feature = "runtime-benchmarks"))]
+	pub fn set_timestamp_unchecked(now: T::Moment, previous: T::Moment, did_update: bool) {
+		<Self as Store>::Now::put(now);
+		<Self as Store>::PreviousNow::put(previous);
+		if did_update {
+			<Self as Store>::DidUpdate::put(true);
+		}
+	}
+
+	/// Advance to the next block and its timestamp by `delta`, running `on_initialize`, setting the
+	/// timestamp like the `set` inherent and running `on_finalize`, so this can be called
+	/// repeatedly to simulate successive blocks. Only used for tests.
+	///
+	/// A `delta` below `block_period()` is rounded up to it, as blocks are produced at most that
+	/// often.
+	#[cfg(feature = "std")]
+	pub fn advance_time_by(delta: T::Moment) {
+		use sr_primitives::traits::{OnInitialize, OnFinalize};
+
+		let n = <system::Module<T>>::block_number() + One::one();
+		<system::Module<T>>::set_block_number(n);
+		<Self as OnInitialize<T::BlockNumber>>::on_initialize(n);
+
+		let now = Self::now_plus(cmp::max(delta, Self::block_period()));
+		Self::try_set(now).expect("timestamp is at least one block period after the last one; qed");
+		<Self as OnFinalize<T::BlockNumber>>::on_finalize(n);
+	}
+
+	/// Seed the timestamps of the blocks in `timestamps`, given in block order, into
+	/// `HistoricalTimestamps`, and make the last two the current and previous timestamp, e.g.
+	/// to replay a recorded chain. Only used for tests.
+	///
+	/// Unlike blocks setting the timestamp, this does not bound the history by
+	/// `Settings::history_depth`.
+	#[cfg(feature = "std")]
+	pub fn seed_timestamps(timestamps: &[(T::BlockNumber, T::Moment)]) {
+		for (block_number, now) in timestamps {
+			<Self as Store>::HistoricalTimestamps::insert(block_number, now);
+			<Self as Store>::PreviousNow::put(Self::now());
+			<Self as Store>::Now::put(now);
+		}
+	}
+
+	/// Restore the timestamps and the bookkeeping derived from them to their genesis values, e.g.
+	/// to reuse test externalities across scenarios. Only used for tests.
+	///
+	/// The configuration, i.e. `BlockPeriod` and `PendingBlockPeriods`, is left as it is. Of the
+	/// `HistoricalTimestamps`, which can't be enumerated, only those of the last
+	/// `Settings::history_depth` blocks are cleared.
+	#[cfg(feature = "std")]
+	pub fn reset_to_genesis() {
+		<Self as Store>::Now::put(Self::genesis_timestamp());
+		<Self as Store>::PreviousNow::kill();
+		<Self as Store>::DidUpdate::kill();
+		<Self as Store>::ScheduleAnchor::kill();
+		<Self as Store>::UpdateCount::kill();
+		<Self as Store>::MaxLag::kill();
+		<Self as Store>::LastSetter::kill();
+		<Self as Store>::RecentDrifts::kill();
+		<Self as Store>::ReportedTimestamps::kill();
+		<Self as Store>::RecentIntervals::kill();
+		<Self as Store>::SetPosition::kill();
+		<Self as Store>::CurrentSlot::kill();
+		for author in <Self as Store>::DriftingAuthors::take() {
+			<Self as Store>::DriftViolations::remove(&author);
+		}
+
+		let current = <system::Module<T>>::block_number();
+		let mut block = current.saturating_sub(T::Settings::history_depth());
+		while block <= current {
+			<Self as Store>::HistoricalTimestamps::remove(&block);
+			block += One::one();
+		}
+	}
+
+	/// Forget that the timestamp was set in this block, so it can be set again. Only used for
+	/// tests and benchmarks.
+	#[cfg(any(feature = "std", feature = "runtime-benchmarks"))]
+	pub fn reset_did_update() {
+		<Self as Store>::DidUpdate::kill();
+	}
 }
 
 fn extract_inherent_data(data: &InherentData) -> Result<InherentType, RuntimeString> {
@@ -296,30 +1941,64 @@ impl<T: Trait> ProvideInherent for Module<T> {
 	const INHERENT_IDENTIFIER: InherentIdentifier = INHERENT_IDENTIFIER;
 
 	fn create_inherent(data: &InherentData) -> Option<Self::Call> {
-		let data: T::Moment = extract_inherent_data(data)
-			.expect("Gets and decodes timestamp inherent data")
-			.saturated_into();
+		let data: T::Moment = match extract_inherent_data(data) {
+			Ok(data) => data.saturated_into(),
+			Err(_e) => {
+				#[cfg(feature = "std")]
+				log::warn!(target: "timestamp", "Not creating the timestamp inherent: {}", _e);
+				return None;
+			}
+		};
 
-		let next_time = cmp::max(data, Self::now() + T::MinimumPeriod::get());
+		let mut period = Self::block_period();
+		if T::Settings::adaptive_inherent() {
+			period = cmp::max(period, Self::average_block_time());
+		}
+		let next_time = cmp::max(
+			T::Settings::inherent_target(data, Self::now(), period),
+			Self::minimum_valid_timestamp(),
+		);
 		Some(Call::set(next_time.into()))
 	}
 
 	fn check_inherent(call: &Self::Call, data: &InherentData) -> result::Result<(), Self::Error> {
-		const MAX_TIMESTAMP_DRIFT: u64 = 60;
-
 		let t: u64 = match call {
-			Call::set(ref t) => t.clone().saturated_into::<u64>(),
+			Call::set(ref t) | Call::set_with_context(ref t, _) => t.clone().saturated_into::<u64>(),
 			_ => return Ok(()),
 		};
 
 		let data = extract_inherent_data(data).map_err(|e| InherentError::Other(e))?;
 
-		let minimum = (Self::now() + T::MinimumPeriod::get()).saturated_into::<u64>();
-		if t > data + MAX_TIMESTAMP_DRIFT {
-			Err(InherentError::Other("Timestamp too far in future to accept".into()))
+		let minimum = Self::checked_minimum_valid_timestamp()
+			.ok_or_else(|| InherentError::Other(OVERFLOW_ERROR.into()))?
+			.saturated_into::<u64>();
+		let max_allowed = data.saturating_add(Self::max_future_drift());
+		let max_past_drift = Self::block_period()
+			.saturated_into::<u64>()
+			.saturating_mul(T::Settings::max_past_drift_periods().into());
+		let earliest = data.saturating_sub(max_past_drift);
+		if t > max_allowed && !T::Settings::disable_future_drift_check() {
+			Err(InherentError::TooFarInFuture { submitted: t, max_allowed })
+		} else if T::Settings::reject_past_slots() && t < Self::earliest_slot_timestamp() {
+			Err(InherentError::Other("Timestamp implies a slot before the current block".into()))
 		} else if t < minimum {
 			Err(InherentError::ValidAtTimestamp(minimum))
+		} else if !max_past_drift.is_zero() && t < earliest {
+			Err(InherentError::TooFarInPast { submitted: t, minimum: earliest })
 		} else {
+			#[cfg(feature = "std")]
+			{
+				if let Some(drift) = Self::significant_drift(t, data) {
+					log::warn!(
+						target: "timestamp",
+						"Accepting timestamp {} running ahead of the local clock by {}, close to the maximum drift of {}",
+						t,
+						drift,
+						Self::max_future_drift(),
+					);
+				}
+			}
+
 			Ok(())
 		}
 	}
@@ -333,84 +2012,3 @@ impl<T: Trait> Time for Module<T> {
 		Self::now()
 	}
 }
-
-#[cfg(test)]
-mod tests {
-	use super::*;
-
-	use srml_support::{impl_outer_origin, assert_ok, parameter_types};
-	use runtime_io::{with_externalities, TestExternalities};
-	use primitives::H256;
-	use sr_primitives::{Perbill, traits::{BlakeTwo256, IdentityLookup}, testing::Header};
-
-	impl_outer_origin! {
-		pub enum Origin for Test {}
-	}
-
-	#[derive(Clone, Eq, PartialEq)]
-	pub struct Test;
-	parameter_types! {
-		pub const BlockHashCount: u64 = 250;
-		pub const MaximumBlockWeight: u32 = 1024;
-		pub const MaximumBlockLength: u32 = 2 * 1024;
-		pub const AvailableBlockRatio: Perbill = Perbill::one();
-	}
-	impl system::Trait for Test {
-		type Origin = Origin;
-		type Index = u64;
-		type BlockNumber = u64;
-		type Call = ();
-		type Hash = H256;
-		type Hashing = BlakeTwo256;
-		type AccountId = u64;
-		type Lookup = IdentityLookup<Self::AccountId>;
-		type Header = Header;
-		type WeightMultiplierUpdate = ();
-		type Event = ();
-		type BlockHashCount = BlockHashCount;
-		type MaximumBlockWeight = MaximumBlockWeight;
-		type AvailableBlockRatio = AvailableBlockRatio;
-		type MaximumBlockLength = MaximumBlockLength;
-		type Version = ();
-	}
-	parameter_types! {
-		pub const MinimumPeriod: u64 = 5;
-	}
-	impl Trait for Test {
-		type Moment = u64;
-		type OnTimestampSet = ();
-		type MinimumPeriod = MinimumPeriod;
-	}
-	type Timestamp = Module<Test>;
-
-	#[test]
-	fn timestamp_works() {
-		let t = system::GenesisConfig::default().build_storage::<Test>().unwrap();
-		with_externalities(&mut TestExternalities::new(t), || {
-			Timestamp::set_timestamp(42);
-			assert_ok!(Timestamp::dispatch(Call::set(69), Origin::NONE));
-			assert_eq!(Timestamp::now(), 69);
-		});
-	}
-
-	#[test]
-	#[should_panic(expected = "Timestamp must be updated only once in the block")]
-	fn double_timestamp_should_fail() {
-		let t = system::GenesisConfig::default().build_storage::<Test>().unwrap();
-		with_externalities(&mut TestExternalities::new(t), || {
-			Timestamp::set_timestamp(42);
-			assert_ok!(Timestamp::dispatch(Call::set(69), Origin::NONE));
-			let _ = Timestamp::dispatch(Call::set(70), Origin::NONE);
-		});
-	}
-
-	#[test]
-	#[should_panic(expected = "Timestamp must increment by at least <MinimumPeriod> between sequential blocks")]
-	fn block_period_minimum_enforced() {
-		let t = system::GenesisConfig::default().build_storage::<Test>().unwrap();
-		with_externalities(&mut TestExternalities::new(t), || {
-			Timestamp::set_timestamp(42);
-			let _ = Timestamp::dispatch(Call::set(46), Origin::NONE);
-		});
-	}
-}
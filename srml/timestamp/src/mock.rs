@@ -0,0 +1,396 @@
+// Copyright 2017-2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Test utilities
+
+#![cfg(test)]
+
+use std::cell::RefCell;
+use codec::Decode;
+use srml_support::{impl_outer_origin, impl_outer_event, parameter_types, traits::{Get, FindAuthor}};
+use sr_primitives::{Perbill, ConsensusEngineId, traits::{BlakeTwo256, IdentityLookup}, testing::Header};
+use primitives::{H256, Blake2Hasher};
+use system::EventRecord;
+use crate::{Trait, Module, GenesisConfig, MAX_TIMESTAMP_DRIFT, OnTimestampSet, LagRecorder, Seconds, Settings};
+
+impl_outer_origin! {
+	pub enum Origin for Test {}
+}
+
+mod timestamp {
+	pub use crate::Event;
+}
+
+impl_outer_event! {
+	pub enum TestEvent for Test {
+		timestamp<T>,
+	}
+}
+
+thread_local! {
+	static TIMESTAMP_DRIFT: RefCell<u64> = RefCell::new(MAX_TIMESTAMP_DRIFT);
+	static PAST_TOLERANCE: RefCell<u64> = RefCell::new(0);
+	static GRACE_PERIOD_BLOCKS: RefCell<u64> = RefCell::new(0);
+	static MAX_PAST_DRIFT_PERIODS: RefCell<u32> = RefCell::new(0);
+	static ADAPTIVE_INHERENT: RefCell<bool> = RefCell::new(false);
+	static CLAMP_INVALID: RefCell<bool> = RefCell::new(false);
+	static REQUIRE_TIMESTAMP_EVERY_BLOCK: RefCell<bool> = RefCell::new(true);
+	static REJECT_PAST_SLOTS: RefCell<bool> = RefCell::new(false);
+	static DISABLE_FUTURE_DRIFT_CHECK: RefCell<bool> = RefCell::new(false);
+	static DRIFT_OFFENCE_THRESHOLD: RefCell<u32> = RefCell::new(0);
+	static MAX_ADAPTIVE_DRIFT: RefCell<u64> = RefCell::new(0);
+	static STRICT_MONOTONIC: RefCell<bool> = RefCell::new(false);
+	static SLOT_DURATION: RefCell<u64> = RefCell::new(0);
+	static SLOW_BLOCK_FACTOR: RefCell<u32> = RefCell::new(0);
+	static FAIL_FALLIBLE_HOOK: RefCell<bool> = RefCell::new(false);
+	static MOMENT_UNIT: RefCell<u64> = RefCell::new(1000);
+	static DETERMINISTIC_INHERENT: RefCell<bool> = RefCell::new(false);
+	pub static EVENTS_SEEN_BY_HOOK: RefCell<Vec<Vec<EventRecord<TestEvent, H256>>>> = Default::default();
+	pub static CONTEXTS_SEEN_BY_HOOK: RefCell<Vec<(u64, u64)>> = Default::default();
+	pub static CHANGES_SEEN_BY_HOOK: RefCell<Vec<(u64, u64, u64)>> = Default::default();
+	pub static DRIFT_OFFENCES: RefCell<Vec<(u64, u32)>> = Default::default();
+	pub static MOMENTS_SEEN_BY_FALLIBLE_HOOK: RefCell<Vec<u64>> = Default::default();
+}
+
+/// Records the events deposited so far whenever the timestamp is set.
+pub struct EventRecorder;
+impl OnTimestampSet<u64> for EventRecorder {
+	fn on_timestamp_set(_: u64) {
+		EVENTS_SEEN_BY_HOOK.with(|e| e.borrow_mut().push(System::events()));
+	}
+}
+
+pub const TEST_ID: ConsensusEngineId = [1, 2, 3, 4];
+
+/// Finds the author encoded in a pre-runtime digest with `TEST_ID`.
+pub struct AuthorGiven;
+impl FindAuthor<u64> for AuthorGiven {
+	fn find_author<'a, I>(digests: I) -> Option<u64>
+		where I: 'a + IntoIterator<Item=(ConsensusEngineId, &'a [u8])>
+	{
+		for (id, data) in digests {
+			if id == TEST_ID {
+				return u64::decode(&mut &data[..]).ok();
+			}
+		}
+
+		None
+	}
+}
+
+pub struct MomentUnit;
+impl Get<u64> for MomentUnit {
+	fn get() -> u64 {
+		MOMENT_UNIT.with(|v| *v.borrow())
+	}
+}
+
+// Workaround for https://github.com/rust-lang/rust/issues/26925 . Remove when sorted.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Test;
+parameter_types! {
+	pub const BlockHashCount: u64 = 250;
+	pub const MaximumBlockWeight: u32 = 1024;
+	pub const MaximumBlockLength: u32 = 2 * 1024;
+	pub const AvailableBlockRatio: Perbill = Perbill::one();
+}
+impl system::Trait for Test {
+	type Origin = Origin;
+	type Index = u64;
+	type BlockNumber = u64;
+	type Call = ();
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type AccountId = u64;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type Header = Header;
+	type WeightMultiplierUpdate = ();
+	type Event = TestEvent;
+	type BlockHashCount = BlockHashCount;
+	type MaximumBlockWeight = MaximumBlockWeight;
+	type AvailableBlockRatio = AvailableBlockRatio;
+	type MaximumBlockLength = MaximumBlockLength;
+	type Version = ();
+}
+parameter_types! {
+	pub const MinimumPeriod: u64 = 5;
+}
+
+/// Reads the settings from the thread locals configured by `ExtBuilder`, and records what the
+/// hooks are called with.
+pub struct TestSettings;
+impl Settings<Test> for TestSettings {
+	fn expected_set_position() -> Option<u32> {
+		Some(0)
+	}
+	fn inherent_target(data: u64, now: u64, period: u64) -> u64 {
+		if DETERMINISTIC_INHERENT.with(|v| *v.borrow()) {
+			now.saturating_add(period)
+		} else {
+			std::cmp::max(data, now.saturating_add(period))
+		}
+	}
+	fn on_before_timestamp_set(old: u64, new: u64) {
+		CHANGES_SEEN_BY_HOOK.with(|c| c.borrow_mut().push((old, new, Timestamp::now())));
+	}
+	fn on_timestamp_set_with_context(moment: u64, block_number: u64) {
+		CONTEXTS_SEEN_BY_HOOK.with(|c| c.borrow_mut().push((moment, block_number)));
+	}
+	fn try_on_timestamp_set(moment: u64) -> Result<(), &'static str> {
+		MOMENTS_SEEN_BY_FALLIBLE_HOOK.with(|m| m.borrow_mut().push(moment));
+		if FAIL_FALLIBLE_HOOK.with(|v| *v.borrow()) {
+			Err("hook failed")
+		} else {
+			Ok(())
+		}
+	}
+	fn max_timestamp_drift() -> u64 {
+		TIMESTAMP_DRIFT.with(|v| *v.borrow())
+	}
+	fn disable_future_drift_check() -> bool {
+		DISABLE_FUTURE_DRIFT_CHECK.with(|v| *v.borrow())
+	}
+	fn max_past_drift_periods() -> u32 {
+		MAX_PAST_DRIFT_PERIODS.with(|v| *v.borrow())
+	}
+	fn reject_past_slots() -> bool {
+		REJECT_PAST_SLOTS.with(|v| *v.borrow())
+	}
+	fn reported_timestamps_window() -> u32 {
+		3
+	}
+	fn max_adaptive_drift() -> u64 {
+		MAX_ADAPTIVE_DRIFT.with(|v| *v.borrow())
+	}
+	fn adaptive_drift_window() -> u32 {
+		3
+	}
+	fn history_depth() -> u64 {
+		3
+	}
+	fn past_tolerance() -> u64 {
+		PAST_TOLERANCE.with(|v| *v.borrow())
+	}
+	fn grace_period_blocks() -> u64 {
+		GRACE_PERIOD_BLOCKS.with(|v| *v.borrow())
+	}
+	fn recent_intervals_window() -> u32 {
+		3
+	}
+	fn require_timestamp_every_block() -> bool {
+		REQUIRE_TIMESTAMP_EVERY_BLOCK.with(|v| *v.borrow())
+	}
+	fn find_author<'a, I>(digests: I) -> Option<u64>
+		where I: 'a + IntoIterator<Item=(ConsensusEngineId, &'a [u8])>
+	{
+		AuthorGiven::find_author(digests)
+	}
+	fn strict_monotonic() -> bool {
+		STRICT_MONOTONIC.with(|v| *v.borrow())
+	}
+	fn drift_offence_threshold() -> u32 {
+		DRIFT_OFFENCE_THRESHOLD.with(|v| *v.borrow())
+	}
+	fn on_drift_offence(offender: &u64, violations: u32) {
+		DRIFT_OFFENCES.with(|o| o.borrow_mut().push((*offender, violations)));
+	}
+	fn max_block_period() -> u64 {
+		1_000
+	}
+	fn slow_block_factor() -> u32 {
+		SLOW_BLOCK_FACTOR.with(|v| *v.borrow())
+	}
+	fn slot_duration() -> u64 {
+		SLOT_DURATION.with(|v| *v.borrow())
+	}
+	fn adaptive_inherent() -> bool {
+		ADAPTIVE_INHERENT.with(|v| *v.borrow())
+	}
+	fn on_invalid_timestamp(_: u64, minimum: u64) -> Option<u64> {
+		if CLAMP_INVALID.with(|v| *v.borrow()) {
+			Some(minimum)
+		} else {
+			None
+		}
+	}
+}
+
+impl Trait for Test {
+	type Event = TestEvent;
+	type Moment = u64;
+	type OnTimestampSet = (EventRecorder, LagRecorder<Test>);
+	type MinimumPeriod = MinimumPeriod;
+	type MomentUnit = MomentUnit;
+	type Settings = TestSettings;
+}
+
+pub type System = system::Module<Test>;
+pub type Timestamp = Module<Test>;
+
+pub struct ExtBuilder {
+	max_timestamp_drift: u64,
+	block_period: u64,
+	past_tolerance: u64,
+	grace_period_blocks: u64,
+	clamp_invalid: bool,
+	max_past_drift_periods: u32,
+	genesis_timestamp: u64,
+	adaptive_inherent: bool,
+	require_timestamp_every_block: bool,
+	reject_past_slots: bool,
+	disable_future_drift_check: bool,
+	drift_offence_threshold: u32,
+	max_adaptive_drift: u64,
+	strict_monotonic: bool,
+	slot_duration: u64,
+	slow_block_factor: u32,
+	fail_fallible_hook: bool,
+	moment_unit: u64,
+	deterministic_inherent: bool,
+}
+
+impl Default for ExtBuilder {
+	fn default() -> Self {
+		Self {
+			max_timestamp_drift: MAX_TIMESTAMP_DRIFT,
+			block_period: 5,
+			past_tolerance: 0,
+			grace_period_blocks: 0,
+			clamp_invalid: false,
+			max_past_drift_periods: 0,
+			genesis_timestamp: 0,
+			adaptive_inherent: false,
+			require_timestamp_every_block: true,
+			reject_past_slots: false,
+			disable_future_drift_check: false,
+			drift_offence_threshold: 0,
+			max_adaptive_drift: 0,
+			strict_monotonic: false,
+			slot_duration: 0,
+			slow_block_factor: 0,
+			fail_fallible_hook: false,
+			moment_unit: Seconds::get(),
+			deterministic_inherent: false,
+		}
+	}
+}
+
+impl ExtBuilder {
+	pub fn max_timestamp_drift(mut self, max_timestamp_drift: u64) -> Self {
+		self.max_timestamp_drift = max_timestamp_drift;
+		self
+	}
+	pub fn block_period(mut self, block_period: u64) -> Self {
+		self.block_period = block_period;
+		self
+	}
+	pub fn past_tolerance(mut self, past_tolerance: u64) -> Self {
+		self.past_tolerance = past_tolerance;
+		self
+	}
+	pub fn clamp_invalid(mut self, clamp_invalid: bool) -> Self {
+		self.clamp_invalid = clamp_invalid;
+		self
+	}
+	pub fn max_past_drift_periods(mut self, max_past_drift_periods: u32) -> Self {
+		self.max_past_drift_periods = max_past_drift_periods;
+		self
+	}
+	pub fn genesis_timestamp(mut self, genesis_timestamp: u64) -> Self {
+		self.genesis_timestamp = genesis_timestamp;
+		self
+	}
+	pub fn adaptive_inherent(mut self, adaptive_inherent: bool) -> Self {
+		self.adaptive_inherent = adaptive_inherent;
+		self
+	}
+	pub fn require_timestamp_every_block(mut self, require_timestamp_every_block: bool) -> Self {
+		self.require_timestamp_every_block = require_timestamp_every_block;
+		self
+	}
+	pub fn reject_past_slots(mut self, reject_past_slots: bool) -> Self {
+		self.reject_past_slots = reject_past_slots;
+		self
+	}
+	pub fn drift_offence_threshold(mut self, drift_offence_threshold: u32) -> Self {
+		self.drift_offence_threshold = drift_offence_threshold;
+		self
+	}
+	pub fn max_adaptive_drift(mut self, max_adaptive_drift: u64) -> Self {
+		self.max_adaptive_drift = max_adaptive_drift;
+		self
+	}
+	pub fn strict_monotonic(mut self, strict_monotonic: bool) -> Self {
+		self.strict_monotonic = strict_monotonic;
+		self
+	}
+	pub fn slot_duration(mut self, slot_duration: u64) -> Self {
+		self.slot_duration = slot_duration;
+		self
+	}
+	pub fn moment_unit(mut self, moment_unit: u64) -> Self {
+		self.moment_unit = moment_unit;
+		self
+	}
+	pub fn deterministic_inherent(mut self, deterministic_inherent: bool) -> Self {
+		self.deterministic_inherent = deterministic_inherent;
+		self
+	}
+	pub fn slow_block_factor(mut self, slow_block_factor: u32) -> Self {
+		self.slow_block_factor = slow_block_factor;
+		self
+	}
+	pub fn grace_period_blocks(mut self, grace_period_blocks: u64) -> Self {
+		self.grace_period_blocks = grace_period_blocks;
+		self
+	}
+	pub fn fail_fallible_hook(mut self, fail_fallible_hook: bool) -> Self {
+		self.fail_fallible_hook = fail_fallible_hook;
+		self
+	}
+	pub fn disable_future_drift_check(mut self, disable_future_drift_check: bool) -> Self {
+		self.disable_future_drift_check = disable_future_drift_check;
+		self
+	}
+	pub fn set_associated_consts(&self) {
+		TIMESTAMP_DRIFT.with(|v| *v.borrow_mut() = self.max_timestamp_drift);
+		PAST_TOLERANCE.with(|v| *v.borrow_mut() = self.past_tolerance);
+		GRACE_PERIOD_BLOCKS.with(|v| *v.borrow_mut() = self.grace_period_blocks);
+		CLAMP_INVALID.with(|v| *v.borrow_mut() = self.clamp_invalid);
+		MAX_PAST_DRIFT_PERIODS.with(|v| *v.borrow_mut() = self.max_past_drift_periods);
+		ADAPTIVE_INHERENT.with(|v| *v.borrow_mut() = self.adaptive_inherent);
+		REQUIRE_TIMESTAMP_EVERY_BLOCK.with(|v| *v.borrow_mut() = self.require_timestamp_every_block);
+		REJECT_PAST_SLOTS.with(|v| *v.borrow_mut() = self.reject_past_slots);
+		DISABLE_FUTURE_DRIFT_CHECK.with(|v| *v.borrow_mut() = self.disable_future_drift_check);
+		DRIFT_OFFENCE_THRESHOLD.with(|v| *v.borrow_mut() = self.drift_offence_threshold);
+		MAX_ADAPTIVE_DRIFT.with(|v| *v.borrow_mut() = self.max_adaptive_drift);
+		STRICT_MONOTONIC.with(|v| *v.borrow_mut() = self.strict_monotonic);
+		SLOT_DURATION.with(|v| *v.borrow_mut() = self.slot_duration);
+		SLOW_BLOCK_FACTOR.with(|v| *v.borrow_mut() = self.slow_block_factor);
+		FAIL_FALLIBLE_HOOK.with(|v| *v.borrow_mut() = self.fail_fallible_hook);
+		MOMENT_UNIT.with(|v| *v.borrow_mut() = self.moment_unit);
+		DETERMINISTIC_INHERENT.with(|v| *v.borrow_mut() = self.deterministic_inherent);
+	}
+	pub fn build(self) -> runtime_io::TestExternalities<Blake2Hasher> {
+		self.set_associated_consts();
+		let mut t = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+		GenesisConfig::<Test> {
+			period: self.block_period,
+			genesis_timestamp: self.genesis_timestamp,
+		}.assimilate_storage(&mut t).unwrap();
+		t.into()
+	}
+}
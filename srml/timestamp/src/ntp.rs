@@ -0,0 +1,249 @@
+// Copyright 2017-2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Clock correcting the system clock against an NTP server.
+
+use std::{io, net::{SocketAddr, ToSocketAddrs, UdpSocket}, time::{Duration, Instant, SystemTime}};
+use inherents::RuntimeString;
+use log::warn;
+use crate::ClockSource;
+
+/// Seconds between the NTP epoch (1900-01-01) and the unix epoch (1970-01-01).
+const NTP_TO_UNIX_SECS: u64 = 2_208_988_800;
+
+/// The length of an NTP packet without extensions.
+const PACKET_LEN: usize = 48;
+
+/// The system clock, corrected by its offset to the clock of an NTP server.
+///
+/// The offset is measured once, when the clock is created. If the server can't be queried the
+/// clock falls back to the plain system clock. Use it with `InherentDataProvider::with_clock`,
+/// which keeps the provided timestamp in the unit of the chain and never decreasing.
+pub struct NtpClock {
+	/// Milliseconds to add to the system clock to get the NTP server's time.
+	offset: i64,
+}
+
+impl NtpClock {
+	/// Create a clock using the offset to the given NTP `server` (e.g. `"pool.ntp.org:123"`),
+	/// waiting at most `timeout` for each of the request and the response.
+	pub fn new<A: ToSocketAddrs>(server: A, timeout: Duration) -> Self {
+		let offset = query_offset(server, timeout).unwrap_or_else(|e| {
+			warn!(
+				target: "timestamp",
+				"Failed to query NTP server, falling back to the system clock: {}",
+				e,
+			);
+			0
+		});
+
+		Self::with_offset(offset)
+	}
+
+	/// Create a clock applying a known `offset`, in milliseconds, to the system clock.
+	pub fn with_offset(offset: i64) -> Self {
+		NtpClock { offset }
+	}
+
+	/// The offset, in milliseconds, applied to the system clock.
+	pub fn offset(&self) -> i64 {
+		self.offset
+	}
+}
+
+impl ClockSource for NtpClock {
+	fn now_since_epoch(&self) -> Result<Duration, RuntimeString> {
+		let now = system_time_millis().map_err(|_| RuntimeString::from("Current time is before unix epoch"))?;
+		let corrected = if self.offset < 0 {
+			now.checked_sub(self.offset.wrapping_neg() as u64)
+		} else {
+			now.checked_add(self.offset as u64)
+		};
+
+		corrected
+			.map(Duration::from_millis)
+			.ok_or_else(|| "Corrected time is out of range".into())
+	}
+}
+
+fn system_time_millis() -> io::Result<u64> {
+	SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)
+		.map(|d| d.as_millis() as u64)
+		.map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+}
+
+fn invalid_data(message: &'static str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Encode the unix time `millis` as an NTP timestamp: 32 bits of seconds and 32 bits of fraction.
+fn encode_ntp_time(millis: u64) -> [u8; 8] {
+	let secs = (millis / 1000 + NTP_TO_UNIX_SECS) as u32;
+	let fraction = (((millis % 1000) << 32) / 1000) as u32;
+	let mut encoded = [0u8; 8];
+	encoded[..4].copy_from_slice(&secs.to_be_bytes());
+	encoded[4..].copy_from_slice(&fraction.to_be_bytes());
+	encoded
+}
+
+/// The client request, carrying `transmit` as its transmit timestamp for the server to echo.
+///
+/// leap indicator: 0, version: 3, mode: 3 (client).
+fn request(transmit: [u8; 8]) -> [u8; PACKET_LEN] {
+	let mut packet = [0u8; PACKET_LEN];
+	packet[0] = 0x1b;
+	packet[40..].copy_from_slice(&transmit);
+	packet
+}
+
+/// The server's transmit time, in milliseconds since the unix epoch, from the `response` to a
+/// request sent with the transmit timestamp `transmit`.
+fn parse_response(response: &[u8], transmit: [u8; 8]) -> io::Result<u64> {
+	if response.len() < PACKET_LEN {
+		return Err(invalid_data("NTP response is too short"));
+	}
+	if response[0] & 0x07 != 4 {
+		return Err(invalid_data("NTP response is not from a server"));
+	}
+	if response[0] >> 6 == 3 {
+		return Err(invalid_data("NTP server is not synchronized"));
+	}
+	match response[1] {
+		0 => return Err(invalid_data("NTP server sent a kiss-o'-death")),
+		stratum if stratum > 15 => return Err(invalid_data("NTP server has an invalid stratum")),
+		_ => {},
+	}
+	// the server echoes the request's transmit timestamp as the originate timestamp.
+	if response[24..32] != transmit[..] {
+		return Err(invalid_data("NTP response does not answer the request"));
+	}
+
+	let secs = u32::from_be_bytes([response[40], response[41], response[42], response[43]]) as u64;
+	let fraction = u32::from_be_bytes([response[44], response[45], response[46], response[47]]) as u64;
+	let secs = secs.checked_sub(NTP_TO_UNIX_SECS)
+		.ok_or_else(|| invalid_data("NTP time is before unix epoch"))?;
+	Ok(secs * 1000 + ((fraction * 1000) >> 32))
+}
+
+/// Measure the offset of the local clock to `server` with a single SNTP request.
+fn query_offset<A: ToSocketAddrs>(server: A, timeout: Duration) -> io::Result<i64> {
+	let server = server.to_socket_addrs()?
+		.next()
+		.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "NTP server has no address"))?;
+	let local = match server {
+		SocketAddr::V4(_) => "0.0.0.0:0",
+		SocketAddr::V6(_) => "[::]:0",
+	};
+	let socket = UdpSocket::bind(local)?;
+	socket.set_read_timeout(Some(timeout))?;
+	socket.set_write_timeout(Some(timeout))?;
+
+	let sent_at = system_time_millis()?;
+	let transmit = encode_ntp_time(sent_at);
+	socket.send_to(&request(transmit), server)?;
+
+	let deadline = Instant::now() + timeout;
+	let mut packet = [0u8; PACKET_LEN];
+	let len = loop {
+		let (len, from) = socket.recv_from(&mut packet)?;
+		if from == server {
+			break len;
+		}
+		if Instant::now() >= deadline {
+			return Err(io::Error::new(io::ErrorKind::TimedOut, "No NTP response from the server"));
+		}
+	};
+	let received_at = system_time_millis()?;
+	let server_time = parse_response(&packet[..len], transmit)?;
+
+	// assume the response was sent half-way through the round trip.
+	let local_time = sent_at + received_at.saturating_sub(sent_at) / 2;
+	Ok(server_time as i64 - local_time as i64)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn now_of(clock: &NtpClock) -> (u64, u64, u64) {
+		let before = system_time_millis().unwrap();
+		let now = clock.now_since_epoch().unwrap().as_millis() as u64;
+		let after = system_time_millis().unwrap();
+		(before, now, after)
+	}
+
+	fn response(transmit: [u8; 8], server_millis: u64) -> [u8; PACKET_LEN] {
+		let mut packet = [0u8; PACKET_LEN];
+		// leap indicator: 0, version: 3, mode: 4 (server), stratum: 2.
+		packet[0] = 0x1c;
+		packet[1] = 2;
+		packet[24..32].copy_from_slice(&transmit);
+		packet[40..].copy_from_slice(&encode_ntp_time(server_millis));
+		packet
+	}
+
+	#[test]
+	fn positive_offset_is_applied() {
+		let clock = NtpClock::with_offset(60_000);
+		let (before, now, after) = now_of(&clock);
+		assert!(now >= before + 60_000 && now <= after + 60_000);
+	}
+
+	#[test]
+	fn negative_offset_is_applied() {
+		let clock = NtpClock::with_offset(-60_000);
+		let (before, now, after) = now_of(&clock);
+		assert!(now >= before - 60_000 && now <= after - 60_000);
+	}
+
+	#[test]
+	fn falls_back_to_system_clock() {
+		// a local socket that never answers.
+		let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+		let clock = NtpClock::new(silent.local_addr().unwrap(), Duration::from_millis(10));
+		assert_eq!(clock.offset(), 0);
+		let (before, now, after) = now_of(&clock);
+		assert!(now >= before && now <= after);
+	}
+
+	#[test]
+	fn parses_server_response() {
+		let transmit = encode_ntp_time(1_568_000_000_123);
+		assert_eq!(parse_response(&response(transmit, 1_568_000_000_500), transmit).unwrap(), 1_568_000_000_500);
+	}
+
+	#[test]
+	fn rejects_invalid_responses() {
+		let transmit = encode_ntp_time(1_568_000_000_123);
+		let valid = response(transmit, 1_568_000_000_500);
+		let rejected = |tweak: &dyn Fn(&mut [u8; PACKET_LEN])| {
+			let mut packet = valid;
+			tweak(&mut packet);
+			parse_response(&packet, transmit).is_err()
+		};
+
+		assert!(parse_response(&valid[..PACKET_LEN - 1], transmit).is_err());
+		// client mode.
+		assert!(rejected(&|p| p[0] = 0x1b));
+		// unsynchronized.
+		assert!(rejected(&|p| p[0] |= 0xc0));
+		// kiss-o'-death.
+		assert!(rejected(&|p| p[1] = 0));
+		assert!(rejected(&|p| p[1] = 16));
+		// answers another request.
+		assert!(rejected(&|p| p[31] ^= 1));
+	}
+}
@@ -0,0 +1,2051 @@
+// Copyright 2017-2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tests for the module.
+
+#![cfg(test)]
+
+use super::*;
+use crate::mock::*;
+use srml_support::{assert_ok, assert_noop};
+use runtime_io::with_externalities;
+use sr_primitives::traits::{OnInitialize, OnFinalize};
+use system::{EventRecord, Phase};
+
+fn inherent_data(timestamp: InherentType) -> InherentData {
+	let mut data = InherentData::new();
+	data.put_data(INHERENT_IDENTIFIER, &timestamp).unwrap();
+	data
+}
+
+fn produce_block(n: u64, now: u64) {
+	System::set_block_number(n);
+	Timestamp::on_initialize(n);
+	assert_ok!(Timestamp::dispatch(Call::set(now), Origin::NONE));
+	Timestamp::on_finalize(n);
+}
+
+#[test]
+fn timestamp_works() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		assert_ok!(Timestamp::dispatch(Call::set(69), Origin::NONE));
+		assert_eq!(Timestamp::now(), 69);
+	});
+}
+
+#[test]
+fn timestamp_set_event_works() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		assert_ok!(Timestamp::dispatch(Call::set(69), Origin::NONE));
+
+		let expected = vec![EventRecord {
+			phase: Phase::Finalization,
+			event: TestEvent::timestamp(RawEvent::TimestampSet(69, 42)),
+			topics: vec![],
+		}];
+		assert_eq!(System::events(), expected);
+		// the event is already deposited by the time the hooks are notified.
+		assert_eq!(EVENTS_SEEN_BY_HOOK.with(|e| e.borrow().clone()), vec![expected]);
+	});
+}
+
+#[test]
+fn set_position_is_recorded() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		System::set_extrinsic_index(0);
+		assert_ok!(Timestamp::dispatch(Call::set(42), Origin::NONE));
+		assert_eq!(Timestamp::set_position(), Some(0));
+		// only `TimestampSet` is deposited at the expected position.
+		assert_eq!(System::events().len(), 1);
+		Timestamp::on_finalize(1);
+
+		Timestamp::on_initialize(2);
+		assert_eq!(Timestamp::set_position(), None);
+		System::set_extrinsic_index(2);
+		assert_ok!(Timestamp::dispatch(Call::set(47), Origin::NONE));
+		assert_eq!(Timestamp::set_position(), Some(2));
+		assert_eq!(
+			System::events().last().map(|r| r.event.clone()),
+			Some(TestEvent::timestamp(RawEvent::UnexpectedSetPosition(0, 2))),
+		);
+	});
+}
+
+#[test]
+fn previous_now_tracks_the_last_timestamp() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		assert_ok!(Timestamp::dispatch(Call::set(42), Origin::NONE));
+		assert_eq!(Timestamp::previous_now(), 0);
+		Timestamp::on_finalize(1);
+
+		assert_ok!(Timestamp::dispatch(Call::set(69), Origin::NONE));
+		assert_eq!(Timestamp::now(), 69);
+		assert_eq!(Timestamp::previous_now(), 42);
+	});
+}
+
+#[test]
+fn try_set_rejects_double_update() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		assert_ok!(Timestamp::try_set(69));
+		assert_eq!(Timestamp::try_set(70), Err(Error::AlreadySet));
+		assert_eq!(Timestamp::now(), 69);
+	});
+}
+
+#[test]
+fn block_period_errors_map_to_variants() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		assert_eq!(Timestamp::ensure_valid_block_period(&0), Err(Error::PeriodZero));
+		assert_eq!(Timestamp::ensure_valid_block_period(&1_001), Err(Error::PeriodTooLarge));
+		assert_eq!(Timestamp::ensure_valid_block_period(&1_000), Ok(()));
+	});
+}
+
+#[test]
+fn errors_convert_to_dispatch_errors() {
+	let error: &'static str = Error::AlreadySet.into();
+	assert_eq!(error, "Timestamp must be updated only once in the block");
+	assert_eq!(<&'static str>::from(Error::Overflow), OVERFLOW_ERROR);
+}
+
+#[test]
+fn try_set_rejects_insufficient_increment() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(
+			Timestamp::try_set(46),
+			Err(Error::InsufficientIncrement),
+		);
+		assert_eq!(Timestamp::now(), 42);
+		assert_ok!(Timestamp::try_set(47));
+	});
+}
+
+#[test]
+fn median_timestamp_of_odd_number_of_samples() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		assert_eq!(Timestamp::median_timestamp(), None);
+		<Timestamp as Store>::ReportedTimestamps::put(vec![30, 10, 20]);
+		assert_eq!(Timestamp::median_timestamp(), Some(20));
+	});
+}
+
+#[test]
+fn median_timestamp_of_even_number_of_samples() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		<Timestamp as Store>::ReportedTimestamps::put(vec![10, 25]);
+		assert_eq!(Timestamp::median_timestamp(), Some(17));
+		<Timestamp as Store>::ReportedTimestamps::put(vec![u64::max_value() - 1, u64::max_value()]);
+		assert_eq!(Timestamp::median_timestamp(), Some(u64::max_value() - 1));
+	});
+}
+
+#[test]
+fn median_timestamp_ignores_outlier() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		<Timestamp as Store>::ReportedTimestamps::put(vec![10, 1_000_000, 20]);
+		assert_eq!(Timestamp::median_timestamp(), Some(20));
+	});
+}
+
+#[test]
+fn set_feeds_reported_timestamps_window() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		produce_block(1, 10);
+		produce_block(2, 20);
+		assert_eq!(Timestamp::reported_timestamps(), vec![10, 20]);
+		produce_block(3, 30);
+		produce_block(4, 1_000);
+		assert_eq!(Timestamp::reported_timestamps(), vec![20, 30, 1_000]);
+		assert_eq!(Timestamp::median_timestamp(), Some(30));
+	});
+}
+
+#[test]
+fn average_block_time_with_partial_window() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		assert_eq!(Timestamp::average_block_time(), 0);
+		produce_block(1, 42);
+		// no interval for the first block.
+		assert_eq!(Timestamp::average_block_time(), 0);
+		produce_block(2, 50);
+		assert_eq!(Timestamp::average_block_time(), 8);
+		produce_block(3, 62);
+		assert_eq!(Timestamp::recent_intervals(), vec![8, 12]);
+		assert_eq!(Timestamp::average_block_time(), 10);
+	});
+}
+
+#[test]
+fn average_block_time_with_full_window() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		produce_block(1, 42);
+		produce_block(2, 50);
+		produce_block(3, 62);
+		produce_block(4, 68);
+		assert_eq!(Timestamp::recent_intervals(), vec![8, 12, 6]);
+		assert_eq!(Timestamp::average_block_time(), 8);
+		produce_block(5, 98);
+		assert_eq!(Timestamp::recent_intervals(), vec![12, 6, 30]);
+		assert_eq!(Timestamp::average_block_time(), 16);
+	});
+}
+
+#[test]
+fn historical_timestamp_within_window() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		produce_block(1, 10);
+		produce_block(2, 20);
+		assert_eq!(Timestamp::historical_timestamp(1), Some(10));
+		assert_eq!(Timestamp::historical_timestamp(2), Some(20));
+	});
+}
+
+#[test]
+fn historical_timestamp_outside_window() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		produce_block(1, 10);
+		assert_eq!(Timestamp::historical_timestamp(0), None);
+		assert_eq!(Timestamp::historical_timestamp(2), None);
+	});
+}
+
+#[test]
+fn was_updated_at_only_knows_recent_blocks() {
+	with_externalities(&mut ExtBuilder::default().require_timestamp_every_block(false).build(), || {
+		for n in 1..=4 {
+			produce_block(n, n * 10);
+		}
+		System::set_block_number(5);
+		Timestamp::on_initialize(5);
+		Timestamp::on_finalize(5);
+
+		assert!(Timestamp::was_updated_at(4));
+		assert!(Timestamp::was_updated_at(3));
+		// pruned after `Settings::history_depth` blocks, even if the last block didn't set its timestamp.
+		assert!(!Timestamp::was_updated_at(2));
+		// never set.
+		assert!(!Timestamp::was_updated_at(5));
+	});
+}
+
+#[test]
+fn historical_timestamps_range_skips_pruned_blocks() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		for n in 1..=5 {
+			produce_block(n, n * 10);
+		}
+		assert_eq!(Timestamp::historical_timestamps_range(3, 5), vec![(3, 30), (4, 40), (5, 50)]);
+		assert_eq!(Timestamp::historical_timestamps_range(1, 4), vec![(3, 30), (4, 40)]);
+		assert_eq!(Timestamp::historical_timestamps_range(4, 3), vec![]);
+		// only the last `Settings::history_depth` blocks of a longer range are read.
+		assert_eq!(Timestamp::historical_timestamps_range(0, 5), vec![(3, 30), (4, 40), (5, 50)]);
+		// the range ends at the current block.
+		assert_eq!(Timestamp::historical_timestamps_range(0, u64::max_value()), vec![(3, 30), (4, 40), (5, 50)]);
+	});
+}
+
+#[test]
+fn historical_timestamps_range_ends_at_current_block() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		for n in 1..=10 {
+			produce_block(n, n * 10);
+		}
+		assert_eq!(Timestamp::historical_timestamps_range(1, 100), vec![(8, 80), (9, 90), (10, 100)]);
+		assert_eq!(Timestamp::historical_timestamps_range(11, 100), vec![]);
+	});
+}
+
+#[test]
+fn historical_timestamps_are_pruned() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		for n in 1..=4 {
+			produce_block(n, n * 10);
+		}
+		assert_eq!(Timestamp::historical_timestamp(1), None);
+		assert_eq!(Timestamp::historical_timestamp(2), Some(20));
+		assert_eq!(Timestamp::historical_timestamp(3), Some(30));
+		assert_eq!(Timestamp::historical_timestamp(4), Some(40));
+	});
+}
+
+#[test]
+fn now_as_millis_works() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(Timestamp::now_as_millis(), 42_000);
+	});
+	with_externalities(&mut ExtBuilder::default().moment_unit(Milliseconds::get()).build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(Timestamp::now_as_millis(), 42);
+	});
+}
+
+#[test]
+fn now_as_millis_saturates() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(u64::max_value() / 1000);
+		assert_eq!(Timestamp::now_as_millis(), u64::max_value() / 1000 * 1000);
+
+		Timestamp::set_timestamp(u64::max_value() / 1000 + 1);
+		assert_eq!(Timestamp::now_as_millis(), u64::max_value());
+	});
+}
+
+#[test]
+fn on_initialize_clears_stuck_update_flag() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		// a previous block set its timestamp but never got finalized.
+		<Timestamp as Store>::DidUpdate::put(true);
+
+		Timestamp::on_initialize(2);
+		assert_ok!(Timestamp::dispatch(Call::set(69), Origin::NONE));
+		assert_eq!(Timestamp::now(), 69);
+	});
+}
+
+#[test]
+#[should_panic(expected = "Timestamp must be updated only once in the block")]
+fn double_timestamp_after_initialize_should_fail() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		Timestamp::on_initialize(1);
+		assert_ok!(Timestamp::dispatch(Call::set(69), Origin::NONE));
+		let _ = Timestamp::dispatch(Call::set(70), Origin::NONE);
+	});
+}
+
+#[test]
+fn set_with_context_records_the_slot() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		produce_block(1, 42);
+
+		System::set_block_number(2);
+		Timestamp::on_initialize(2);
+		assert_ok!(Timestamp::dispatch(Call::set_with_context(48, 7), Origin::NONE));
+		assert_eq!(Timestamp::now(), 48);
+		assert_eq!(Timestamp::current_slot(), Some(7));
+		assert_eq!(Timestamp::previous_now(), 42);
+		Timestamp::on_finalize(2);
+
+		// the slot is only kept for the block that set it.
+		Timestamp::on_initialize(3);
+		assert_eq!(Timestamp::current_slot(), None);
+	});
+}
+
+#[test]
+#[should_panic(expected = "Timestamp must increment by at least <MinimumPeriod> between sequential blocks")]
+fn set_with_context_enforces_the_minimum_period() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		let _ = Timestamp::dispatch(Call::set_with_context(46, 7), Origin::NONE);
+	});
+}
+
+#[test]
+fn set_with_context_inherent_is_checked_like_set() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(Timestamp::check_inherent(&Call::set_with_context(47, 7), &inherent_data(47)), Ok(()));
+		assert_eq!(
+			Timestamp::check_inherent(&Call::set_with_context(44, 7), &inherent_data(47)),
+			Err(InherentError::ValidAtTimestamp(47)),
+		);
+	});
+}
+
+#[test]
+fn elapsed_works() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		produce_block(1, 42);
+		assert_eq!(Timestamp::elapsed(), 0);
+		produce_block(2, 50);
+		assert_eq!(Timestamp::elapsed(), 8);
+		produce_block(3, 70);
+		assert_eq!(Timestamp::elapsed(), 20);
+	});
+}
+
+#[test]
+#[should_panic(expected = "Timestamp must be updated only once in the block")]
+fn double_timestamp_should_fail() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		assert_ok!(Timestamp::dispatch(Call::set(69), Origin::NONE));
+		let _ = Timestamp::dispatch(Call::set(70), Origin::NONE);
+	});
+}
+
+#[test]
+#[should_panic(expected = "Timestamp must increment by at least <MinimumPeriod> between sequential blocks")]
+fn block_period_minimum_enforced() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		let _ = Timestamp::dispatch(Call::set(46), Origin::NONE);
+	});
+}
+
+#[test]
+fn genesis_presets_set_the_block_period() {
+	ExtBuilder::default().set_associated_consts();
+	assert_eq!(GenesisConfig::<Test>::with_block_period(5).period, 5);
+	assert_eq!(GenesisConfig::<Test>::with_three_second_blocks().period, 3);
+	assert_eq!(GenesisConfig::<Test>::with_six_second_blocks().period, 6);
+	assert_eq!(GenesisConfig::<Test>::with_six_second_blocks().genesis_timestamp, 0);
+
+	ExtBuilder::default().moment_unit(Milliseconds::get()).set_associated_consts();
+	assert_eq!(GenesisConfig::<Test>::with_six_second_blocks().period, 6_000);
+}
+
+#[test]
+fn block_period_defaults_to_minimum_period() {
+	// no timestamp genesis at all.
+	let t = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	with_externalities(&mut runtime_io::TestExternalities::new(t), || {
+		assert_eq!(Timestamp::minimum_period(), 5);
+		assert_eq!(Timestamp::block_period(), 5);
+	});
+	with_externalities(&mut ExtBuilder::default().block_period(3).build(), || {
+		assert_eq!(Timestamp::block_period(), 5);
+	});
+}
+
+#[test]
+#[should_panic(expected = "Timestamp block period must be non-zero")]
+fn genesis_block_period_must_be_non_zero() {
+	let _ = GenesisConfig::<Test> { period: 0, genesis_timestamp: 0 }.build_storage();
+}
+
+#[test]
+fn advised_period_is_not_enforced_by_set() {
+	with_externalities(&mut ExtBuilder::default().block_period(10).build(), || {
+		assert_eq!(Timestamp::minimum_period(), 5);
+		assert_eq!(Timestamp::block_period(), 10);
+
+		Timestamp::set_timestamp(42);
+		// satisfies the minimum period but undershoots the advised one.
+		assert_ok!(Timestamp::dispatch(Call::set(48), Origin::NONE));
+		assert_eq!(Timestamp::now(), 48);
+	});
+}
+
+#[test]
+fn deterministic_inherent_ignores_local_clock() {
+	with_externalities(&mut ExtBuilder::default().block_period(10).deterministic_inherent(true).build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(Timestamp::create_inherent(&inherent_data(48)), Some(Call::set(52)));
+		assert_eq!(Timestamp::create_inherent(&inherent_data(60)), Some(Call::set(52)));
+	});
+}
+
+#[test]
+fn create_inherent_targets_advised_period() {
+	with_externalities(&mut ExtBuilder::default().block_period(10).build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(Timestamp::create_inherent(&inherent_data(48)), Some(Call::set(52)));
+		assert_eq!(Timestamp::create_inherent(&inherent_data(60)), Some(Call::set(60)));
+		assert_eq!(Timestamp::check_inherent(&Call::set(48), &inherent_data(48)), Ok(()));
+	});
+}
+
+#[test]
+fn increment_check_ignores_changed_block_period() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		assert_ok!(Timestamp::dispatch(Call::set_block_period(100), Origin::ROOT));
+		Timestamp::set_timestamp(42);
+
+		assert_eq!(Timestamp::create_inherent(&inherent_data(47)), Some(Call::set(142)));
+		assert_eq!(Timestamp::check_inherent(&Call::set(47), &inherent_data(47)), Ok(()));
+		assert_eq!(
+			Timestamp::check_inherent(&Call::set(46), &inherent_data(47)),
+			Err(InherentError::ValidAtTimestamp(47)),
+		);
+		assert_ok!(Timestamp::try_set(47));
+	});
+}
+
+#[test]
+fn force_clear_did_update_allows_next_set() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		assert_ok!(Timestamp::dispatch(Call::set(42), Origin::NONE));
+		assert_noop!(
+			Timestamp::dispatch(Call::force_clear_did_update(), Origin::signed(1)),
+			"bad origin: expected to be a root origin"
+		);
+		assert!(Timestamp::is_updated());
+
+		assert_ok!(Timestamp::dispatch(Call::force_clear_did_update(), Origin::ROOT));
+		assert!(!Timestamp::is_updated());
+		assert_ok!(Timestamp::dispatch(Call::set(47), Origin::NONE));
+	});
+}
+
+#[test]
+fn set_block_period_requires_root() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		assert_noop!(
+			Timestamp::dispatch(Call::set_block_period(10), Origin::signed(1)),
+			"bad origin: expected to be a root origin"
+		);
+		assert_eq!(Timestamp::block_period(), 5);
+	});
+}
+
+#[test]
+fn set_block_period_rejects_zero() {
+	with_externalities(&mut ExtBuilder::default().block_period(10).build(), || {
+		assert_noop!(
+			Timestamp::dispatch(Call::set_block_period(0), Origin::ROOT),
+			"Block period must be non-zero"
+		);
+		assert_eq!(Timestamp::block_period(), 10);
+	});
+}
+
+#[test]
+fn set_block_period_works() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		assert_ok!(Timestamp::dispatch(Call::set_block_period(10), Origin::ROOT));
+		assert_eq!(Timestamp::block_period(), 10);
+
+		Timestamp::set_timestamp(42);
+		assert_eq!(Timestamp::create_inherent(&inherent_data(42)), Some(Call::set(52)));
+	});
+}
+
+#[test]
+fn past_tolerance_widens_lower_bound() {
+	with_externalities(&mut ExtBuilder::default().past_tolerance(2).build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(Timestamp::check_inherent(&Call::set(45), &inherent_data(45)), Ok(()));
+		assert_eq!(
+			Timestamp::check_inherent(&Call::set(44), &inherent_data(45)),
+			Err(InherentError::ValidAtTimestamp(45)),
+		);
+		assert_eq!(
+			Timestamp::try_set(44),
+			Err(Error::InsufficientIncrement),
+		);
+		// exactly at the edge of the tolerance.
+		assert_ok!(Timestamp::try_set(45));
+	});
+}
+
+#[test]
+fn grace_period_only_requires_non_decreasing_timestamps() {
+	with_externalities(&mut ExtBuilder::default().grace_period_blocks(2).build(), || {
+		produce_block(1, 42);
+		// within the grace period.
+		produce_block(2, 43);
+		assert_eq!(Timestamp::now(), 43);
+
+		// after it.
+		System::set_block_number(3);
+		Timestamp::on_initialize(3);
+		assert_eq!(Timestamp::minimum_valid_timestamp(), 48);
+		assert_eq!(Timestamp::try_set(47), Err(Error::InsufficientIncrement));
+		assert_ok!(Timestamp::try_set(48));
+	});
+}
+
+#[test]
+fn strict_monotonic_rejects_equal_timestamps() {
+	with_externalities(&mut ExtBuilder::default().past_tolerance(10).strict_monotonic(true).build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(
+			Timestamp::check_inherent(&Call::set(42), &inherent_data(45)),
+			Err(InherentError::ValidAtTimestamp(43)),
+		);
+		assert_eq!(
+			Timestamp::try_set(42),
+			Err(Error::InsufficientIncrement),
+		);
+		assert_ok!(Timestamp::try_set(43));
+	});
+}
+
+#[test]
+fn past_tolerance_never_allows_going_backwards() {
+	with_externalities(&mut ExtBuilder::default().past_tolerance(10).build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(
+			Timestamp::check_inherent(&Call::set(41), &inherent_data(45)),
+			Err(InherentError::ValidAtTimestamp(42)),
+		);
+		assert_ok!(Timestamp::try_set(42));
+	});
+}
+
+#[test]
+fn inherent_data_provider_never_goes_backwards() {
+	let provider = InherentDataProvider::default();
+	assert_eq!(provider.monotonic(100), 100);
+	// the wall clock jumps backwards.
+	assert_eq!(provider.monotonic(90), 100);
+	assert_eq!(provider.monotonic(100), 100);
+	assert_eq!(provider.monotonic(110), 110);
+}
+
+#[test]
+fn inherent_data_provider_provides_current_time() {
+	let provider = InherentDataProvider::default();
+	let mut data = InherentData::new();
+	provider.provide_inherent_data(&mut data).unwrap();
+	let first = data.timestamp_inherent_data().unwrap();
+	let mut data = InherentData::new();
+	provider.provide_inherent_data(&mut data).unwrap();
+	assert!(data.timestamp_inherent_data().unwrap() >= first);
+}
+
+#[test]
+fn timestamp_override_is_parsed_as_unix_secs() {
+	use std::ffi::OsStr;
+
+	assert_eq!(parse_timestamp_override(OsStr::new("1568000000")), Ok(Duration::from_secs(1_568_000_000)));
+	assert!(parse_timestamp_override(OsStr::new("")).is_err());
+	assert!(parse_timestamp_override(OsStr::new("1568000000.5")).is_err());
+	assert!(parse_timestamp_override(OsStr::new("-1")).is_err());
+	assert!(parse_timestamp_override(OsStr::new("tomorrow")).is_err());
+}
+
+#[test]
+fn inherent_data_provider_reads_custom_clock() {
+	struct FixedClock(Duration);
+	impl ClockSource for FixedClock {
+		fn now_since_epoch(&self) -> Result<Duration, RuntimeString> {
+			Ok(self.0)
+		}
+	}
+
+	let provided = |millis| {
+		let provider = InherentDataProvider::with_clock(millis, FixedClock(Duration::from_millis(42_123)));
+		let mut data = InherentData::new();
+		provider.provide_inherent_data(&mut data).unwrap();
+		data.timestamp_inherent_data().unwrap()
+	};
+	assert_eq!(provided(true), 42_123);
+	assert_eq!(provided(false), 42);
+}
+
+#[test]
+fn encoded_timestamp_inherent_decodes_to_moment() {
+	let encoded = encode_timestamp_inherent(42_000);
+	assert_eq!(InherentType::decode(&mut &encoded[..]).ok(), Some(42_000));
+
+	let mut data = InherentData::new();
+	data.put_data(INHERENT_IDENTIFIER, &42_000u64).unwrap();
+	assert_eq!(data.get_data::<InherentType>(&INHERENT_IDENTIFIER), Ok(Some(42_000)));
+	assert_eq!(extract_inherent_data(&data).map(|t| encode_timestamp_inherent(t)), Ok(encoded));
+}
+
+#[test]
+fn inherent_data_provider_future_bias_stays_below_drift() {
+	struct FixedClock;
+	impl ClockSource for FixedClock {
+		fn now_since_epoch(&self) -> Result<Duration, RuntimeString> {
+			Ok(Duration::from_secs(1_000))
+		}
+	}
+
+	let provided = |millis, bias| {
+		let provider = InherentDataProvider::with_clock(millis, FixedClock).with_future_bias_secs(bias);
+		let mut data = InherentData::new();
+		provider.provide_inherent_data(&mut data).unwrap();
+		data.timestamp_inherent_data().unwrap()
+	};
+	assert_eq!(provided(false, 0), 1_000);
+	assert_eq!(provided(false, 5), 1_005);
+	assert_eq!(provided(false, 100), 1_000 + MAX_TIMESTAMP_DRIFT - 1);
+	assert_eq!(provided(true, 5), 1_005_000);
+	assert_eq!(provided(true, 100), 1_000_000 + MAX_TIMESTAMP_DRIFT * 1000 - 1);
+}
+
+#[test]
+fn inherent_data_provider_clamps_to_ceiling() {
+	struct FixedClock(u64);
+	impl ClockSource for FixedClock {
+		fn now_since_epoch(&self) -> Result<Duration, RuntimeString> {
+			Ok(Duration::from_secs(self.0))
+		}
+	}
+
+	let provided = |secs| {
+		let provider = InherentDataProvider::with_clock(false, FixedClock(secs))
+			.with_future_bias_secs(5)
+			.with_ceiling_secs(1_000);
+		let mut data = InherentData::new();
+		provider.provide_inherent_data(&mut data).map(|_| data.timestamp_inherent_data().unwrap())
+	};
+	assert_eq!(provided(990), Ok(995));
+	// the bias is clamped to the ceiling.
+	assert_eq!(provided(998), Ok(1_000));
+	assert_eq!(provided(1_000), Ok(1_000));
+	assert_eq!(provided(1_001), Err("Current time is past the timestamp ceiling".into()));
+}
+
+#[test]
+fn inherent_data_provider_keeps_last_check_decision() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		let provider = InherentDataProvider::default();
+		assert_eq!(provider.last_check_decision(), None);
+
+		Timestamp::set_timestamp(42);
+		let period = Timestamp::block_period();
+		let check = |t| {
+			let result = Timestamp::check_inherent(&Call::set(t), &inherent_data(50));
+			provider.record_check_decision(t, 50, period, result)
+		};
+
+		assert_eq!(check(47), Ok(()));
+		assert_eq!(check(44), Err(InherentError::ValidAtTimestamp(47)));
+		assert_eq!(provider.last_check_decision(), Some(CheckDecision {
+			submitted: 44,
+			now: 50,
+			period: 5,
+			outcome: Err(InherentError::ValidAtTimestamp(47)),
+		}));
+		assert_eq!(provider.check_outcomes(), CheckOutcomes { ok: 1, valid_at_timestamp: 1, rejected: 0 });
+	});
+}
+
+#[test]
+fn inherent_data_provider_reports_last_drift() {
+	let provider = InherentDataProvider::default();
+	assert_eq!(provider.reported_drift(), None);
+
+	provider.record_drift(1_042, 1_000);
+	assert_eq!(provider.reported_drift(), Some(42));
+	provider.record_drift(990, 1_000);
+	assert_eq!(provider.reported_drift(), Some(-10));
+	provider.record_drift(u64::max_value(), 0);
+	assert_eq!(provider.reported_drift(), Some(i64::max_value()));
+}
+
+#[test]
+fn inherent_data_provider_tallies_check_outcomes() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		let provider = InherentDataProvider::default();
+		Timestamp::set_timestamp(42);
+		let check = |t| provider.record_check(Timestamp::check_inherent(&Call::set(t), &inherent_data(50)));
+
+		assert_eq!(check(47), Ok(()));
+		assert_eq!(check(48), Ok(()));
+		assert_eq!(check(44), Err(InherentError::ValidAtTimestamp(47)));
+		assert!(check(1_000).is_err());
+		assert_eq!(provider.check_outcomes(), CheckOutcomes { ok: 2, valid_at_timestamp: 1, rejected: 1 });
+	});
+}
+
+#[test]
+fn inherent_data_provider_leap_second_policy() {
+	struct FixedClock(u64);
+	impl ClockSource for FixedClock {
+		fn now_since_epoch(&self) -> Result<Duration, RuntimeString> {
+			Ok(Duration::from_millis(self.0))
+		}
+	}
+
+	let provided = |policy, millis| {
+		let provider = InherentDataProvider::with_clock(true, FixedClock(millis)).with_leap_second_policy(policy);
+		let mut data = InherentData::new();
+		provider.provide_inherent_data(&mut data).unwrap();
+		data.timestamp_inherent_data().unwrap()
+	};
+	// the leap second at the end of 2016.
+	let leap = 1_483_228_800_000;
+	let half_window = 12 * 60 * 60 * 1000;
+
+	assert_eq!(provided(LeapSecondPolicy::Ignore, leap - 500), leap - 500);
+	// a smeared clock is half a second behind when the leap second starts.
+	assert_eq!(provided(LeapSecondPolicy::Smear, leap - 500), leap - 1_000);
+	assert_eq!(provided(LeapSecondPolicy::Smear, leap - half_window - 1), leap - half_window - 1);
+	assert_eq!(provided(LeapSecondPolicy::Smear, leap + half_window - 1_000), leap + half_window - 1_000);
+}
+
+#[test]
+fn inherent_data_provider_precision() {
+	use std::time::SystemTime;
+
+	let provided = |provider: InherentDataProvider| {
+		let mut data = InherentData::new();
+		provider.provide_inherent_data(&mut data).unwrap();
+		data.timestamp_inherent_data().unwrap()
+	};
+	let since_epoch = || SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+
+	let before = since_epoch();
+	let secs = provided(InherentDataProvider::new(false));
+	let millis = provided(InherentDataProvider::new(true));
+	let after = since_epoch();
+
+	assert!(secs >= before.as_secs() && secs <= after.as_secs());
+	assert!(millis >= before.as_millis() as u64 && millis <= after.as_millis() as u64);
+	assert!(millis > secs);
+	assert!(provided(InherentDataProvider::default()) >= millis);
+}
+
+#[test]
+fn overflowing_minimum_period_is_rejected() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(u64::max_value() - 2);
+		assert_eq!(Timestamp::try_set(u64::max_value()), Err(Error::Overflow));
+
+		let call = Call::set(u64::max_value());
+		assert_eq!(
+			Timestamp::check_inherent(&call, &inherent_data(u64::max_value())),
+			Err(InherentError::Other(OVERFLOW_ERROR.into())),
+		);
+	});
+}
+
+#[test]
+fn reset_did_update_allows_setting_again() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		assert_ok!(Timestamp::dispatch(Call::set(69), Origin::NONE));
+		Timestamp::reset_did_update();
+		assert_ok!(Timestamp::dispatch(Call::set(74), Origin::NONE));
+		assert_eq!(Timestamp::now(), 74);
+	});
+}
+
+#[test]
+fn hook_with_context_sees_block_number() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		produce_block(1, 42);
+		produce_block(2, 69);
+		assert_eq!(CONTEXTS_SEEN_BY_HOOK.with(|c| c.borrow().clone()), vec![(42, 1), (69, 2)]);
+	});
+}
+
+#[test]
+fn failing_fallible_hook_does_not_abort_set() {
+	with_externalities(&mut ExtBuilder::default().fail_fallible_hook(true).build(), || {
+		produce_block(1, 42);
+		produce_block(2, 69);
+		assert_eq!(Timestamp::now(), 69);
+		assert_eq!(Timestamp::previous_now(), 42);
+		// the hook still saw every timestamp.
+		assert_eq!(MOMENTS_SEEN_BY_FALLIBLE_HOOK.with(|m| m.borrow().clone()), vec![42, 69]);
+	});
+}
+
+#[test]
+fn deadlines() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+
+		assert!(Timestamp::is_expired(41));
+		assert_eq!(Timestamp::time_until(41), 0);
+
+		assert!(Timestamp::is_expired(42));
+		assert_eq!(Timestamp::time_until(42), 0);
+
+		assert!(!Timestamp::is_expired(50));
+		assert_eq!(Timestamp::time_until(50), 8);
+	});
+}
+
+#[test]
+fn blocks_until_rounds_down_at_the_block_period() {
+	with_externalities(&mut ExtBuilder::default().block_period(6).build(), || {
+		Timestamp::set_timestamp(42);
+
+		// in the past.
+		assert_eq!(Timestamp::blocks_until(30), 0);
+		assert_eq!(Timestamp::blocks_until(42), 0);
+		// near, less than a block away.
+		assert_eq!(Timestamp::blocks_until(47), 0);
+		assert_eq!(Timestamp::blocks_until(48), 1);
+		// far.
+		assert_eq!(Timestamp::blocks_until(42 + 6 * 1_000_000 + 5), 1_000_000);
+		assert_eq!(Timestamp::blocks_until(u64::max_value()), (u64::max_value() - 42) / 6);
+	});
+}
+
+#[test]
+fn now_as_datetime() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(1_500_000_000);
+		assert_eq!(
+			Timestamp::now_as_datetime(),
+			Some(chrono::NaiveDate::from_ymd(2017, 7, 14).and_hms(2, 40, 0)),
+		);
+
+		Timestamp::set_timestamp(u64::max_value());
+		assert_eq!(Timestamp::now_as_datetime(), None);
+		Timestamp::set_timestamp(i64::max_value() as u64);
+		assert_eq!(Timestamp::now_as_datetime(), None);
+	});
+}
+
+#[test]
+fn now_as_datetime_follows_moment_unit() {
+	with_externalities(&mut ExtBuilder::default().moment_unit(Milliseconds::get()).build(), || {
+		Timestamp::set_timestamp(1_500_000_000_999);
+		assert_eq!(
+			Timestamp::now_as_datetime(),
+			Some(chrono::NaiveDate::from_ymd(2017, 7, 14).and_hms(2, 40, 0)),
+		);
+
+		Timestamp::set_timestamp(u64::max_value());
+		assert_eq!(Timestamp::now_as_datetime(), None);
+	});
+}
+
+#[test]
+fn is_updated_within_block() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::on_initialize(1);
+		assert!(!Timestamp::is_updated());
+		assert_ok!(Timestamp::dispatch(Call::set(42), Origin::NONE));
+		assert!(Timestamp::is_updated());
+		// reading the flag doesn't consume it.
+		assert!(Timestamp::is_updated());
+		Timestamp::on_finalize(1);
+	});
+}
+
+#[test]
+fn significant_drift_is_flagged() {
+	with_externalities(&mut ExtBuilder::default().max_timestamp_drift(60).build(), || {
+		Timestamp::set_timestamp(42);
+
+		assert_eq!(Timestamp::significant_drift(100, 100), None);
+		assert_eq!(Timestamp::significant_drift(130, 100), None);
+		assert_eq!(Timestamp::significant_drift(131, 100), Some(31));
+		assert_eq!(Timestamp::significant_drift(160, 100), Some(60));
+
+		// warning about the drift doesn't affect the result.
+		assert_eq!(Timestamp::check_inherent(&Call::set(160), &inherent_data(100)), Ok(()));
+	});
+}
+
+#[test]
+fn clamp_policy_coerces_under_incremented_timestamp() {
+	with_externalities(&mut ExtBuilder::default().clamp_invalid(true).build(), || {
+		Timestamp::set_timestamp(42);
+		assert_ok!(Timestamp::dispatch(Call::set(44), Origin::NONE));
+		assert_eq!(Timestamp::now(), 47);
+	});
+}
+
+#[test]
+fn reject_policy_keeps_rejecting() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(
+			Timestamp::try_set(44),
+			Err(Error::InsufficientIncrement),
+		);
+		assert_eq!(Timestamp::now(), 42);
+	});
+}
+
+#[test]
+fn default_drift_is_exposed() {
+	assert_eq!(crate::MAX_TIMESTAMP_DRIFT, 60);
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		let drift = crate::MAX_TIMESTAMP_DRIFT;
+		assert_eq!(Timestamp::check_inherent(&Call::set(100 + drift), &inherent_data(100)), Ok(()));
+		assert!(Timestamp::check_inherent(&Call::set(101 + drift), &inherent_data(100)).is_err());
+	});
+}
+
+#[test]
+fn timestamp_inherent_data_in_millis() {
+	let data = inherent_data(42);
+	assert_eq!(data.timestamp_inherent_data(), Ok(42));
+	assert_eq!(data.timestamp_inherent_data_millis(Seconds::get()), Ok(42_000));
+	assert_eq!(data.timestamp_inherent_data_millis(Milliseconds::get()), Ok(42));
+
+	let data = inherent_data(u64::max_value());
+	assert_eq!(data.timestamp_inherent_data_millis(Seconds::get()), Ok(u64::max_value()));
+	assert!(InherentData::new().timestamp_inherent_data_millis(Seconds::get()).is_err());
+}
+
+#[test]
+fn mildly_past_timestamp_is_retried() {
+	with_externalities(&mut ExtBuilder::default().max_past_drift_periods(20).build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(
+			Timestamp::check_inherent(&Call::set(44), &inherent_data(50)),
+			Err(InherentError::ValidAtTimestamp(47)),
+		);
+		assert_eq!(Timestamp::check_inherent(&Call::set(950), &inherent_data(1000)), Ok(()));
+	});
+}
+
+#[test]
+fn absurdly_past_timestamp_is_reported_but_not_fatal() {
+	with_externalities(&mut ExtBuilder::default().max_past_drift_periods(20).build(), || {
+		Timestamp::set_timestamp(42);
+		let err = Timestamp::check_inherent(&Call::set(48), &inherent_data(1000)).unwrap_err();
+		assert_eq!(err, InherentError::TooFarInPast { submitted: 48, minimum: 900 });
+		// Every historical block lags this far behind the clock of a syncing node.
+		assert!(!err.is_fatal_error());
+		assert_eq!(
+			Timestamp::check_inherent(&Call::set(899), &inherent_data(1000)),
+			Err(InherentError::TooFarInPast { submitted: 899, minimum: 900 }),
+		);
+		// The fatal and retryable checks come first.
+		assert_eq!(
+			Timestamp::check_inherent(&Call::set(44), &inherent_data(1000)),
+			Err(InherentError::ValidAtTimestamp(47)),
+		);
+	});
+}
+
+#[test]
+fn past_drift_is_counted_in_block_periods() {
+	with_externalities(&mut ExtBuilder::default().max_past_drift_periods(20).build(), || {
+		Timestamp::set_timestamp(42);
+		assert_ok!(Timestamp::dispatch(Call::set_block_period(10), Origin::ROOT));
+		assert_eq!(Timestamp::check_inherent(&Call::set(850), &inherent_data(1000)), Ok(()));
+		assert_eq!(
+			Timestamp::check_inherent(&Call::set(799), &inherent_data(1000)),
+			Err(InherentError::TooFarInPast { submitted: 799, minimum: 800 }),
+		);
+	});
+}
+
+#[test]
+fn timestamp_implying_past_slot_is_fatal() {
+	with_externalities(&mut ExtBuilder::default().reject_past_slots(true).build(), || {
+		// block 10 can't be in a slot before the tenth 5 second period since genesis.
+		System::set_block_number(10);
+		Timestamp::set_timestamp(42);
+		let err = Timestamp::check_inherent(&Call::set(47), &inherent_data(50)).unwrap_err();
+		assert_eq!(err, InherentError::Other("Timestamp implies a slot before the current block".into()));
+		assert!(err.is_fatal_error());
+		assert_eq!(Timestamp::check_inherent(&Call::set(50), &inherent_data(50)), Ok(()));
+	});
+}
+
+#[test]
+fn past_slot_check_follows_block_period_changes() {
+	with_externalities(&mut ExtBuilder::default().reject_past_slots(true).build(), || {
+		let produce_honest_block = |n: u64, t: u64| {
+			System::set_block_number(n);
+			Timestamp::on_initialize(n);
+			assert_eq!(Timestamp::expected_timestamp(n), t);
+			assert_eq!(Timestamp::check_inherent(&Call::set(t), &inherent_data(t)), Ok(()));
+			assert_ok!(Timestamp::dispatch(Call::set(t), Origin::NONE));
+			Timestamp::on_finalize(n);
+		};
+
+		for n in 1..=4 {
+			produce_honest_block(n, n * 5);
+		}
+		// takes effect from the next block.
+		assert_ok!(Timestamp::dispatch(Call::set_block_period(10), Origin::ROOT));
+		assert_eq!(Timestamp::expected_timestamp(4), 20);
+		produce_honest_block(5, 30);
+		produce_honest_block(6, 40);
+
+		assert_ok!(Timestamp::dispatch(Call::schedule_block_period_change(7, 20), Origin::ROOT));
+		produce_honest_block(7, 60);
+		produce_honest_block(8, 80);
+		assert_eq!(Timestamp::schedule_anchor(), Some((6, 40)));
+	});
+}
+
+#[test]
+fn past_slots_are_accepted_by_default() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		System::set_block_number(10);
+		Timestamp::set_timestamp(42);
+		assert_eq!(Timestamp::check_inherent(&Call::set(47), &inherent_data(50)), Ok(()));
+	});
+}
+
+#[test]
+fn future_drift_widens_with_recent_drifts_up_to_cap() {
+	let builder = ExtBuilder::default().block_period(10).max_timestamp_drift(2).max_adaptive_drift(3);
+	with_externalities(&mut builder.build(), || {
+		assert_eq!(Timestamp::max_future_drift(), 2);
+		produce_block(1, 11);
+		produce_block(2, 22);
+		assert_eq!(Timestamp::recent_drifts(), vec![1, 2]);
+		assert_eq!(Timestamp::max_future_drift(), 3);
+
+		// consistently offset clocks widen the drift up to the cap.
+		produce_block(3, 34);
+		produce_block(4, 44);
+		produce_block(5, 54);
+		assert_eq!(Timestamp::recent_drifts(), vec![4, 4, 4]);
+		assert_eq!(Timestamp::max_future_drift(), 5);
+		assert_eq!(Timestamp::check_inherent(&Call::set(65), &inherent_data(60)), Ok(()));
+		assert_eq!(
+			Timestamp::check_inherent(&Call::set(66), &inherent_data(60)),
+			Err(InherentError::TooFarInFuture { submitted: 66, max_allowed: 65 }),
+		);
+	});
+}
+
+#[test]
+fn future_drift_check_can_be_disabled() {
+	let far_future = 1_000_000;
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(
+			Timestamp::check_inherent(&Call::set(far_future), &inherent_data(47)),
+			Err(InherentError::TooFarInFuture { submitted: far_future, max_allowed: 47 + MAX_TIMESTAMP_DRIFT }),
+		);
+	});
+	with_externalities(&mut ExtBuilder::default().disable_future_drift_check(true).build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(Timestamp::check_inherent(&Call::set(far_future), &inherent_data(47)), Ok(()));
+		// the other checks still apply.
+		assert_eq!(
+			Timestamp::check_inherent(&Call::set(44), &inherent_data(47)),
+			Err(InherentError::ValidAtTimestamp(47)),
+		);
+	});
+}
+
+#[test]
+fn future_drift_is_fixed_by_default() {
+	with_externalities(&mut ExtBuilder::default().block_period(10).max_timestamp_drift(2).build(), || {
+		produce_block(1, 14);
+		produce_block(2, 24);
+		assert!(Timestamp::recent_drifts().is_empty());
+		assert_eq!(Timestamp::max_future_drift(), 2);
+	});
+}
+
+#[test]
+fn past_drift_is_unbounded_by_default() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(Timestamp::check_inherent(&Call::set(47), &inherent_data(1_000_000)), Ok(()));
+	});
+}
+
+#[test]
+fn first_block_must_follow_genesis_timestamp() {
+	with_externalities(&mut ExtBuilder::default().genesis_timestamp(1_000).build(), || {
+		assert_eq!(Timestamp::now(), 1_000);
+		assert_eq!(
+			Timestamp::try_set(1_004),
+			Err(Error::InsufficientIncrement),
+		);
+		assert_ok!(Timestamp::try_set(1_005));
+		assert_eq!(Timestamp::previous_now(), 1_000);
+	});
+}
+
+#[test]
+fn duration_between() {
+	assert_eq!(Timestamp::duration_between(42, 69), 27);
+	assert_eq!(Timestamp::duration_between(42, 42), 0);
+	assert_eq!(Timestamp::duration_between(69, 42), 0);
+}
+
+#[test]
+fn lag_recorder_counts_blocks_ahead_of_schedule_as_no_lag() {
+	with_externalities(&mut ExtBuilder::default().block_period(10).genesis_timestamp(1_000).build(), || {
+		// ahead of the expected 1_010.
+		produce_block(1, 1_005);
+		assert_eq!(Timestamp::max_lag(), 0);
+
+		// a longer block period only applies to the blocks after the change.
+		assert_ok!(Timestamp::dispatch(Call::set_block_period(100), Origin::ROOT));
+		produce_block(2, 1_150);
+		assert_eq!(Timestamp::expected_timestamp(2), 1_110);
+		assert_eq!(Timestamp::max_lag(), 40);
+	});
+}
+
+#[test]
+fn lag_recorder_keeps_maximum_lag() {
+	with_externalities(&mut ExtBuilder::default().genesis_timestamp(1_000).build(), || {
+		produce_block(1, 1_005);
+		assert_eq!(Timestamp::max_lag(), 0);
+		// produced 3 seconds after the expected 1_010.
+		produce_block(2, 1_013);
+		assert_eq!(Timestamp::max_lag(), 3);
+		produce_block(3, 1_030);
+		assert_eq!(Timestamp::max_lag(), 15);
+		// catching up doesn't reduce the maximum.
+		produce_block(4, 1_035);
+		assert_eq!(Timestamp::max_lag(), 15);
+		produce_block(5, 1_040);
+		assert_eq!(Timestamp::max_lag(), 15);
+	});
+}
+
+#[test]
+fn try_now_within_block() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		produce_block(1, 42);
+
+		Timestamp::on_initialize(2);
+		assert_eq!(Timestamp::try_now(), None);
+		assert_eq!(Timestamp::now(), 42);
+		assert_ok!(Timestamp::dispatch(Call::set(69), Origin::NONE));
+		assert_eq!(Timestamp::try_now(), Some(69));
+	});
+}
+
+#[test]
+fn now_finalized_reads_the_current_block_timestamp() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		produce_block(1, 42);
+
+		Timestamp::on_initialize(2);
+		assert_ok!(Timestamp::dispatch(Call::set(69), Origin::NONE));
+		assert_eq!(Timestamp::now_finalized(), 69);
+	});
+}
+
+#[test]
+#[should_panic(expected = "Timestamp must be set before reading the finalized timestamp")]
+fn now_finalized_panics_before_set() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		produce_block(1, 42);
+
+		Timestamp::on_initialize(2);
+		assert_eq!(Timestamp::now(), 42);
+		Timestamp::now_finalized();
+	});
+}
+
+#[test]
+fn create_inherent_without_data() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		assert_eq!(Timestamp::create_inherent(&InherentData::new()), None);
+
+		let mut data = InherentData::new();
+		data.put_data(INHERENT_IDENTIFIER, &42u8).unwrap();
+		assert_eq!(Timestamp::create_inherent(&data), None);
+	});
+}
+
+#[test]
+fn set_has_operational_weight() {
+	use sr_primitives::weights::{GetDispatchInfo, DispatchInfo, DispatchClass};
+
+	assert_eq!(
+		Call::<Test>::set(42).get_dispatch_info(),
+		DispatchInfo { weight: SET_WEIGHT, class: DispatchClass::Operational },
+	);
+}
+
+#[test]
+fn set_timestamp_unchecked_writes_directly() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp_unchecked(42, 69, false);
+		assert_eq!(Timestamp::now(), 42);
+		assert_eq!(Timestamp::previous_now(), 69);
+		assert!(!Timestamp::is_updated());
+		assert_eq!(Timestamp::elapsed(), 0);
+		assert_ok!(Timestamp::try_set(47));
+
+		Timestamp::reset_did_update();
+		Timestamp::set_timestamp_unchecked(100, 90, true);
+		assert_eq!(Timestamp::try_now(), Some(100));
+		assert_eq!(Timestamp::elapsed(), 10);
+		assert_eq!(Timestamp::try_set(105), Err(Error::AlreadySet));
+	});
+}
+
+#[test]
+fn chain_stall_detection() {
+	// healthy: the last block is recent, or even ahead of the local clock.
+	assert!(!is_chain_stalled(1_000, 1_006, 6));
+	assert!(!is_chain_stalled(1_000, 1_060, 6));
+	assert!(!is_chain_stalled(1_000, 990, 6));
+	// stalled: more than ten periods without a block.
+	assert!(is_chain_stalled(1_000, 1_061, 6));
+	assert!(is_chain_stalled(0, u64::max_value(), u64::max_value() / 20));
+}
+
+#[test]
+fn migrate_moment_scale_from_secs_to_millis() {
+	let builder = ExtBuilder::default().block_period(6).genesis_timestamp(30).max_adaptive_drift(3);
+	with_externalities(&mut builder.build(), || {
+		produce_block(1, 42);
+		produce_block(2, 49);
+		assert_ok!(Timestamp::dispatch(Call::schedule_block_period_change(5, 10), Origin::ROOT));
+
+		Timestamp::migrate_moment_scale(1_000);
+		assert_eq!(Timestamp::now(), 49_000);
+		assert_eq!(Timestamp::previous_now(), 42_000);
+		assert_eq!(Timestamp::block_period(), 6_000);
+		assert_eq!(Timestamp::genesis_timestamp(), 30_000);
+		assert_eq!(Timestamp::reported_timestamps(), vec![42_000, 49_000]);
+		assert_eq!(Timestamp::recent_intervals(), vec![12_000, 7_000]);
+		assert_eq!(Timestamp::recent_drifts(), vec![0, 1_000]);
+		assert_eq!(Timestamp::pending_block_period(5), Some(10_000));
+
+		// the new runtime keeps counting forwards.
+		produce_block(3, 55_000);
+	});
+}
+
+#[test]
+fn migrate_previous_now_initializes_it_to_now() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		// a chain upgrading from a runtime without `PreviousNow`.
+		Timestamp::set_timestamp(42);
+		assert!(!<Timestamp as Store>::PreviousNow::exists());
+
+		Timestamp::migrate_previous_now();
+		assert_eq!(Timestamp::previous_now(), 42);
+		assert_eq!(Timestamp::now(), 42);
+
+		produce_block(2, 47);
+		assert_eq!(Timestamp::elapsed(), 5);
+
+		// running the migration again doesn't overwrite it.
+		Timestamp::migrate_previous_now();
+		assert_eq!(Timestamp::previous_now(), 42);
+	});
+}
+
+#[test]
+fn migrate_moment_scale_saturates() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(u64::max_value() / 2);
+		Timestamp::migrate_moment_scale(1_000);
+		assert_eq!(Timestamp::now(), u64::max_value());
+	});
+}
+
+#[test]
+fn adaptive_inherent_compensates_slow_blocks() {
+	let slow_blocks = || {
+		produce_block(1, 10);
+		produce_block(2, 30);
+		produce_block(3, 50);
+		assert_eq!(Timestamp::average_block_time(), 20);
+	};
+
+	with_externalities(&mut ExtBuilder::default().adaptive_inherent(true).build(), || {
+		slow_blocks();
+		assert_eq!(Timestamp::create_inherent(&inherent_data(51)), Some(Call::set(70)));
+		assert_eq!(Timestamp::create_inherent(&inherent_data(80)), Some(Call::set(80)));
+	});
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		slow_blocks();
+		assert_eq!(Timestamp::create_inherent(&inherent_data(51)), Some(Call::set(55)));
+	});
+}
+
+#[test]
+fn adaptive_inherent_never_undershoots_block_period() {
+	with_externalities(&mut ExtBuilder::default().block_period(10).adaptive_inherent(true).build(), || {
+		produce_block(1, 10);
+		produce_block(2, 15);
+		assert_eq!(Timestamp::create_inherent(&inherent_data(16)), Some(Call::set(25)));
+	});
+}
+
+#[test]
+fn minimum_valid_timestamp() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(Timestamp::minimum_valid_timestamp(), 47);
+		assert_ok!(Timestamp::try_set(47));
+
+		Timestamp::set_timestamp(u64::max_value() - 2);
+		assert_eq!(Timestamp::minimum_valid_timestamp(), u64::max_value());
+	});
+	with_externalities(&mut ExtBuilder::default().past_tolerance(2).build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(Timestamp::minimum_valid_timestamp(), 45);
+	});
+}
+
+#[test]
+fn hook_before_set_sees_old_and_new_timestamp() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		produce_block(1, 42);
+		produce_block(2, 69);
+		assert_eq!(
+			CHANGES_SEEN_BY_HOOK.with(|c| c.borrow().clone()),
+			vec![(0, 42, 0), (42, 69, 42)],
+		);
+	});
+}
+
+#[test]
+fn scheduled_block_period_change_applies_at_block() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		System::set_block_number(1);
+		assert_ok!(Timestamp::dispatch(Call::schedule_block_period_change(3, 10), Origin::ROOT));
+		assert_eq!(Timestamp::pending_block_period(3), Some(10));
+
+		produce_block(1, 5);
+		produce_block(2, 10);
+		assert_eq!(Timestamp::block_period(), 5);
+		produce_block(3, 15);
+		assert_eq!(Timestamp::block_period(), 10);
+		assert_eq!(Timestamp::pending_block_period(3), None);
+	});
+}
+
+#[test]
+fn schedule_block_period_change_checks() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		System::set_block_number(2);
+		assert_noop!(
+			Timestamp::dispatch(Call::schedule_block_period_change(3, 10), Origin::signed(1)),
+			"bad origin: expected to be a root origin"
+		);
+		assert_noop!(
+			Timestamp::dispatch(Call::schedule_block_period_change(3, 0), Origin::ROOT),
+			"Block period must be non-zero"
+		);
+		assert_noop!(
+			Timestamp::dispatch(Call::schedule_block_period_change(2, 10), Origin::ROOT),
+			"Block period change must be scheduled for a future block"
+		);
+	});
+}
+
+#[test]
+fn now_plus_saturates() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(Timestamp::now_plus(27), 69);
+		assert_eq!(Timestamp::now_plus(u64::max_value() - 1), u64::max_value());
+	});
+}
+
+#[test]
+fn advance_time_by_simulates_blocks() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::advance_time_by(10);
+		assert_eq!((Timestamp::now(), Timestamp::previous_now()), (10, 0));
+		Timestamp::advance_time_by(10);
+		assert_eq!((Timestamp::now(), Timestamp::previous_now()), (20, 10));
+		// rounded up to the block period.
+		Timestamp::advance_time_by(1);
+		assert_eq!((Timestamp::now(), Timestamp::previous_now()), (25, 20));
+		assert!(!Timestamp::is_updated());
+		assert_eq!(System::block_number(), 3);
+		assert_eq!(Timestamp::historical_timestamps_range(1, 3), vec![(1, 10), (2, 20), (3, 25)]);
+	});
+}
+
+#[test]
+fn advance_time_by_follows_block_period() {
+	with_externalities(&mut ExtBuilder::default().block_period(20).build(), || {
+		Timestamp::advance_time_by(1);
+		assert_eq!(Timestamp::now(), 20);
+		Timestamp::advance_time_by(30);
+		assert_eq!(Timestamp::now(), 50);
+
+		// a scheduled change of the period applies at its block.
+		assert_ok!(Timestamp::dispatch(Call::schedule_block_period_change(4, 40), Origin::ROOT));
+		Timestamp::advance_time_by(1);
+		assert_eq!(Timestamp::now(), 70);
+		Timestamp::advance_time_by(1);
+		assert_eq!(Timestamp::now(), 110);
+	});
+}
+
+#[test]
+fn block_period_above_maximum_is_rejected() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		assert_ok!(Timestamp::dispatch(Call::set_block_period(1_000), Origin::ROOT));
+		assert_noop!(
+			Timestamp::dispatch(Call::set_block_period(1_001), Origin::ROOT),
+			"Block period must not exceed Settings::max_block_period"
+		);
+		assert_noop!(
+			Timestamp::dispatch(Call::schedule_block_period_change(1, 1_001), Origin::ROOT),
+			"Block period must not exceed Settings::max_block_period"
+		);
+		assert_eq!(Timestamp::block_period(), 1_000);
+	});
+}
+
+#[test]
+#[should_panic(expected = "Timestamp block period must not exceed Settings::max_block_period")]
+fn genesis_block_period_above_maximum_is_rejected() {
+	let _ = ExtBuilder::default().block_period(1_001).build();
+}
+
+#[test]
+#[should_panic(expected = "Timestamp block period must divide the slot duration")]
+fn genesis_block_period_not_dividing_slot_duration_is_rejected() {
+	let _ = ExtBuilder::default().block_period(5).slot_duration(12).build();
+}
+
+#[test]
+fn genesis_block_period_dividing_slot_duration_is_accepted() {
+	with_externalities(&mut ExtBuilder::default().block_period(5).slot_duration(10).build(), || {
+		assert_eq!(Timestamp::block_period(), 5);
+	});
+}
+
+#[test]
+fn check_set_reports_already_set() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(Timestamp::check_set(44), Err(InherentError::ValidAtTimestamp(47)));
+		assert_eq!(Timestamp::check_set(47), Ok(()));
+
+		assert_ok!(Timestamp::dispatch(Call::set(47), Origin::NONE));
+		let err = Timestamp::check_set(52).unwrap_err();
+		assert_eq!(err, InherentError::AlreadySet);
+		assert!(err.is_fatal_error());
+		assert_eq!(InherentError::try_from(&INHERENT_IDENTIFIER, &err.encode()), Some(InherentError::AlreadySet));
+	});
+}
+
+#[test]
+fn duration_conversions() {
+	use std::time::Duration;
+
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		assert_eq!(Timestamp::moment_to_duration(42), Duration::from_secs(42));
+		assert_eq!(Timestamp::duration_to_moment(Duration::from_millis(42_999)), 42);
+		for &moment in &[0, 1, 1_500_000_000, u64::max_value() / 1000] {
+			assert_eq!(Timestamp::duration_to_moment(Timestamp::moment_to_duration(moment)), moment);
+		}
+		assert_eq!(Timestamp::moment_to_duration(u64::max_value()), Duration::from_millis(u64::max_value()));
+	});
+	with_externalities(&mut ExtBuilder::default().moment_unit(Milliseconds::get()).build(), || {
+		assert_eq!(Timestamp::moment_to_duration(42), Duration::from_millis(42));
+		assert_eq!(Timestamp::duration_to_moment(Duration::from_micros(42_999)), 42);
+		for &moment in &[0, 1, 1_500_000_000, u64::max_value() / 1000] {
+			assert_eq!(Timestamp::duration_to_moment(Timestamp::moment_to_duration(moment)), moment);
+		}
+		assert_eq!(Timestamp::duration_to_moment(Duration::from_secs(u64::max_value())), u64::max_value());
+	});
+}
+
+#[test]
+fn last_setter_is_block_author() {
+	use sr_primitives::testing::{Digest, DigestItem};
+
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		let mut digest = Digest::default();
+		digest.logs.push(DigestItem::PreRuntime(TEST_ID, 11u64.encode()));
+		System::initialize(&1, &Default::default(), &Default::default(), &digest);
+		assert_ok!(Timestamp::dispatch(Call::set(42), Origin::NONE));
+		assert_eq!(Timestamp::last_setter(), Some(11));
+		Timestamp::on_finalize(1);
+
+		// the author of the next block is unknown.
+		System::initialize(&2, &Default::default(), &Default::default(), &Default::default());
+		assert_ok!(Timestamp::dispatch(Call::set(47), Origin::NONE));
+		assert_eq!(Timestamp::last_setter(), None);
+	});
+}
+
+#[test]
+fn since_genesis_is_time_elapsed_since_genesis_timestamp() {
+	with_externalities(&mut ExtBuilder::default().genesis_timestamp(1_000).build(), || {
+		assert_eq!(Timestamp::since_genesis(), 0);
+		assert_ok!(Timestamp::dispatch(Call::set(1_042), Origin::NONE));
+		assert_eq!(Timestamp::since_genesis(), 42);
+	});
+}
+
+#[test]
+fn inherent_error_variant_indices_are_stable() {
+	// nodes and runtimes of different versions exchange these, so the indices must never change.
+	let index = |error: InherentError| error.encode()[0];
+	assert_eq!(index(InherentError::ValidAtTimestamp(42)), 0);
+	assert_eq!(index(InherentError::Other("error".into())), 1);
+	assert_eq!(index(InherentError::TooFarInFuture { submitted: 2, max_allowed: 1 }), 2);
+	assert_eq!(index(InherentError::TooFarInPast { submitted: 1, minimum: 2 }), 3);
+	assert_eq!(index(InherentError::AlreadySet), 4);
+}
+
+#[test]
+fn inherent_error_decoding_reports_failures() {
+	let encoded = InherentError::ValidAtTimestamp(42).encode();
+	assert_eq!(
+		InherentError::decode_from(&INHERENT_IDENTIFIER, &encoded),
+		Ok(InherentError::ValidAtTimestamp(42)),
+	);
+	assert_eq!(
+		InherentError::decode_from(b"othrinh0", &encoded),
+		Err(InherentErrorDecodeError::WrongIdentifier),
+	);
+	assert_eq!(
+		InherentError::decode_from(&INHERENT_IDENTIFIER, &[]),
+		Err(InherentErrorDecodeError::Undecodable { remaining: 0 }),
+	);
+	match InherentError::decode_from(&INHERENT_IDENTIFIER, &encoded[..5]) {
+		Err(InherentErrorDecodeError::Undecodable { remaining }) => assert!(remaining < 5),
+		other => panic!("truncated payload decoded as {:?}", other),
+	}
+	assert_eq!(InherentError::try_from(&INHERENT_IDENTIFIER, &encoded[..5]), None);
+
+	let provider = InherentDataProvider::default();
+	assert!(provider.error_to_string(&encoded[..5]).unwrap().starts_with("Corrupted"));
+}
+
+#[test]
+fn scale_by_blocks_saturates() {
+	assert_eq!(Timestamp::scale_by_blocks(6, 10), 60);
+	assert_eq!(Timestamp::checked_scale_by_blocks(6, 10), Some(60));
+
+	let large = u64::max_value() / 2;
+	assert_eq!(Timestamp::scale_by_blocks(6, large), u64::max_value());
+	assert_eq!(Timestamp::checked_scale_by_blocks(6, large), None);
+	assert_eq!(Timestamp::checked_scale_by_blocks(0, u64::max_value()), Some(0));
+}
+
+#[test]
+#[should_panic(expected = "Timestamp must be updated once in the block")]
+fn block_without_timestamp_panics_when_required() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::on_initialize(1);
+		Timestamp::on_finalize(1);
+	});
+}
+
+#[test]
+fn slow_block_event_only_fires_for_slow_blocks() {
+	with_externalities(&mut ExtBuilder::default().slow_block_factor(3).build(), || {
+		let slow_blocks = || System::events().into_iter().filter_map(|r| match r.event {
+			TestEvent::timestamp(RawEvent::SlowBlock(interval)) => Some(interval),
+			_ => None,
+		}).collect::<Vec<_>>();
+
+		produce_block(1, 42);
+		produce_block(2, 47);
+		// exactly at the threshold of 3 block periods.
+		produce_block(3, 62);
+		assert_eq!(slow_blocks(), Vec::<u64>::new());
+
+		produce_block(4, 100);
+		assert_eq!(slow_blocks(), vec![38]);
+	});
+}
+
+#[test]
+fn block_without_timestamp_is_allowed_when_not_required() {
+	with_externalities(&mut ExtBuilder::default().require_timestamp_every_block(false).build(), || {
+		produce_block(1, 42);
+		Timestamp::on_initialize(2);
+		Timestamp::on_finalize(2);
+		assert_eq!(Timestamp::now(), 42);
+		assert_eq!(
+			System::events().last().map(|r| r.event.clone()),
+			Some(TestEvent::timestamp(RawEvent::TimestampMissing(2))),
+		);
+		produce_block(3, 47);
+		assert_eq!(Timestamp::now(), 47);
+	});
+}
+
+#[test]
+fn slot_duration_is_block_period_in_millis() {
+	with_externalities(&mut ExtBuilder::default().block_period(6).build(), || {
+		assert_eq!(Timestamp::slot_duration(), 6_000);
+	});
+	let builder = ExtBuilder::default().block_period(6).moment_unit(Milliseconds::get());
+	with_externalities(&mut builder.build(), || {
+		assert_eq!(Timestamp::slot_duration(), 6);
+	});
+}
+
+#[test]
+fn seeded_timestamps_are_retrievable() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::seed_timestamps(&[(1, 6), (2, 12), (3, 18)]);
+		assert_eq!(Timestamp::historical_timestamp(1), Some(6));
+		assert_eq!(Timestamp::historical_timestamp(2), Some(12));
+		assert_eq!(Timestamp::historical_timestamp(3), Some(18));
+		assert_eq!(Timestamp::now(), 18);
+		assert_eq!(Timestamp::elapsed(), 6);
+	});
+}
+
+#[test]
+fn now_ceil_to_period_rounds_up_to_boundary() {
+	with_externalities(&mut ExtBuilder::default().block_period(6).genesis_timestamp(4).build(), || {
+		Timestamp::set_timestamp(16);
+		assert_eq!(Timestamp::now_ceil_to_period(), 16);
+		Timestamp::set_timestamp(17);
+		assert_eq!(Timestamp::now_ceil_to_period(), 22);
+		Timestamp::set_timestamp(u64::max_value() - 1);
+		assert_eq!(Timestamp::now_ceil_to_period(), u64::max_value());
+	});
+}
+
+#[test]
+fn consecutive_drifting_timestamps_are_reported() {
+	use sr_primitives::testing::{Digest, DigestItem};
+
+	let builder = ExtBuilder::default().block_period(10).max_timestamp_drift(1).drift_offence_threshold(3);
+	with_externalities(&mut builder.build(), || {
+		let produce_block_by = |author: u64, n: u64, now: u64| {
+			let mut digest = Digest::default();
+			digest.logs.push(DigestItem::PreRuntime(TEST_ID, author.encode()));
+			System::initialize(&n, &Default::default(), &Default::default(), &digest);
+			Timestamp::on_initialize(n);
+			assert_ok!(Timestamp::dispatch(Call::set(now), Origin::NONE));
+			Timestamp::on_finalize(n);
+		};
+		let reported = || DRIFT_OFFENCES.with(|o| o.borrow().clone());
+
+		// an isolated drifting timestamp is forgotten.
+		produce_block_by(7, 1, 13);
+		assert_eq!(Timestamp::drift_violations(7), 1);
+		assert_eq!(Timestamp::drifting_authors(), vec![7]);
+		produce_block_by(7, 2, 20);
+		assert_eq!(Timestamp::drift_violations(7), 0);
+		assert!(Timestamp::drifting_authors().is_empty());
+
+		produce_block_by(7, 3, 33);
+		produce_block_by(7, 4, 43);
+		// another author's drift doesn't count.
+		produce_block_by(8, 5, 55);
+		assert!(reported().is_empty());
+
+		produce_block_by(7, 6, 67);
+		assert_eq!(reported(), vec![(7, 3)]);
+		assert_eq!(Timestamp::drift_violations(7), 0);
+	});
+}
+
+#[test]
+fn drift_offences_follow_block_period_changes() {
+	use sr_primitives::testing::{Digest, DigestItem};
+
+	let builder = ExtBuilder::default().block_period(10).max_timestamp_drift(1).drift_offence_threshold(3);
+	with_externalities(&mut builder.build(), || {
+		for n in 1..=3 {
+			produce_block(n, n * 10);
+		}
+		assert_ok!(Timestamp::dispatch(Call::set_block_period(25), Origin::ROOT));
+
+		// on the slots of the new period, but not on multiples of it since genesis.
+		let mut digest = Digest::default();
+		digest.logs.push(DigestItem::PreRuntime(TEST_ID, 7u64.encode()));
+		for (n, now) in vec![(4, 55), (5, 80), (6, 105)] {
+			System::initialize(&n, &Default::default(), &Default::default(), &digest);
+			Timestamp::on_initialize(n);
+			assert_ok!(Timestamp::dispatch(Call::set(now), Origin::NONE));
+			Timestamp::on_finalize(n);
+		}
+		assert_eq!(Timestamp::drift_violations(7), 0);
+		assert!(DRIFT_OFFENCES.with(|o| o.borrow().is_empty()));
+	});
+}
+
+#[test]
+fn now_is_stored_under_now_storage_key() {
+	with_externalities(&mut ExtBuilder::default().genesis_timestamp(42).build(), || {
+		let stored = runtime_io::storage(&Timestamp::now_storage_key()).unwrap();
+		assert_eq!(u64::decode(&mut &stored[..]).ok(), Some(Timestamp::now()));
+		assert_eq!(Timestamp::now(), 42);
+	});
+}
+
+#[test]
+fn dependents_can_be_generic_over_time() {
+	use srml_support::traits::Time;
+
+	struct FixedTime;
+	impl Time for FixedTime {
+		type Moment = u64;
+		fn now() -> u64 {
+			42
+		}
+	}
+
+	fn is_expired<T: Time>(deadline: T::Moment) -> bool {
+		T::now() >= deadline
+	}
+
+	assert!(is_expired::<FixedTime>(42));
+	assert!(!is_expired::<FixedTime>(43));
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(43);
+		assert!(is_expired::<Timestamp>(43));
+		assert!(!is_expired::<Timestamp>(44));
+	});
+}
+
+#[test]
+fn update_count_counts_timestamped_blocks() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		assert_eq!(Timestamp::update_count(), 0);
+		produce_block(1, 42);
+		produce_block(2, 47);
+		produce_block(3, 52);
+		assert_eq!(Timestamp::update_count(), 3);
+	});
+}
+
+#[test]
+fn expected_timestamp_assumes_perfect_cadence() {
+	with_externalities(&mut ExtBuilder::default().block_period(6).genesis_timestamp(30).build(), || {
+		assert_eq!(Timestamp::expected_timestamp(0), 30);
+		assert_eq!(Timestamp::expected_timestamp(100), 630);
+		assert_eq!(Timestamp::expected_timestamp(u64::max_value() / 2), u64::max_value());
+	});
+}
+
+#[test]
+fn timestamp_seed_changes_with_timestamp() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		produce_block(1, 42);
+		let first = Timestamp::timestamp_seed();
+		assert_eq!(Timestamp::timestamp_seed(), first);
+		produce_block(2, 47);
+		assert_ne!(Timestamp::timestamp_seed(), first);
+	});
+}
+
+#[test]
+fn now_as_secs_is_independent_of_moment_unit() {
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(Timestamp::now_as_secs(), 42);
+	});
+	with_externalities(&mut ExtBuilder::default().moment_unit(Milliseconds::get()).build(), || {
+		Timestamp::set_timestamp(42_999);
+		assert_eq!(Timestamp::now_as_secs(), 42);
+	});
+}
+
+#[test]
+fn unix_secs_round_trip() {
+	for &unit in &[Seconds::get(), Milliseconds::get()] {
+		with_externalities(&mut ExtBuilder::default().moment_unit(unit).build(), || {
+			for &secs in &[0, 1, 1_568_000_000, u64::max_value() / 1000] {
+				assert_eq!(Timestamp::to_unix_secs(Timestamp::from_unix_secs(secs)), secs);
+			}
+		});
+	}
+}
+
+#[test]
+fn unix_secs_conversions_saturate() {
+	with_externalities(&mut ExtBuilder::default().moment_unit(Milliseconds::get()).build(), || {
+		assert_eq!(Timestamp::from_unix_secs(1_568_000_000), 1_568_000_000_000);
+		assert_eq!(Timestamp::from_unix_secs(u64::max_value()), u64::max_value());
+		assert_eq!(Timestamp::to_unix_secs(1_999), 1);
+	});
+	with_externalities(&mut ExtBuilder::default().build(), || {
+		assert_eq!(Timestamp::from_unix_secs(u64::max_value()), u64::max_value());
+		assert_eq!(Timestamp::to_unix_secs(u64::max_value()), u64::max_value());
+	});
+}
+
+#[test]
+fn reset_to_genesis_restores_genesis_state() {
+	use sr_primitives::testing::{Digest, DigestItem};
+
+	let builder = ExtBuilder::default()
+		.genesis_timestamp(30)
+		.block_period(10)
+		.max_timestamp_drift(1)
+		.drift_offence_threshold(3);
+	with_externalities(&mut builder.build(), || {
+		produce_block(1, 42);
+		produce_block(2, 47);
+
+		let mut digest = Digest::default();
+		digest.logs.push(DigestItem::PreRuntime(TEST_ID, 7u64.encode()));
+		System::initialize(&3, &Default::default(), &Default::default(), &digest);
+		Timestamp::on_initialize(3);
+		System::set_extrinsic_index(0);
+		assert_ok!(Timestamp::dispatch(Call::set_with_context(53, 2), Origin::NONE));
+		assert_eq!(Timestamp::drift_violations(7), 1);
+
+		Timestamp::reset_to_genesis();
+		assert_eq!(Timestamp::now(), 30);
+		assert_eq!(Timestamp::previous_now(), 0);
+		assert!(!Timestamp::is_updated());
+		assert_eq!(Timestamp::update_count(), 0);
+		assert_eq!(Timestamp::max_lag(), 0);
+		assert_eq!(Timestamp::last_setter(), None);
+		assert_eq!(Timestamp::set_position(), None);
+		assert_eq!(Timestamp::current_slot(), None);
+		assert_eq!(Timestamp::drift_violations(7), 0);
+		assert!(Timestamp::drifting_authors().is_empty());
+		assert!(Timestamp::reported_timestamps().is_empty());
+		assert!(Timestamp::recent_intervals().is_empty());
+		assert_eq!(Timestamp::historical_timestamps_range(0, 3), vec![]);
+
+		// the chain can start again from genesis.
+		assert_ok!(Timestamp::dispatch(Call::set(35), Origin::NONE));
+	});
+}
+
+#[test]
+fn next_block_deadline_is_one_period_after_now() {
+	with_externalities(&mut ExtBuilder::default().block_period(6).build(), || {
+		Timestamp::set_timestamp(1_000_000);
+		assert_eq!(Timestamp::next_block_deadline_unix_secs(), 1_000_006);
+	});
+	let builder = ExtBuilder::default().moment_unit(Milliseconds::get()).block_period(1_000);
+	with_externalities(&mut builder.build(), || {
+		Timestamp::set_timestamp(1_000_000_500);
+		assert_eq!(Timestamp::next_block_deadline_unix_secs(), 1_000_001);
+	});
+}
+
+#[test]
+fn zero_drift_rejects_any_timestamp_ahead_of_local_clock() {
+	with_externalities(&mut ExtBuilder::default().max_timestamp_drift(0).build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(Timestamp::check_inherent(&Call::set(100), &inherent_data(100)), Ok(()));
+		assert_eq!(
+			Timestamp::check_inherent(&Call::set(101), &inherent_data(100)),
+			Err(InherentError::TooFarInFuture { submitted: 101, max_allowed: 100 }),
+		);
+	});
+}
+
+#[test]
+fn large_drift_accepts_timestamps_far_ahead_of_local_clock() {
+	with_externalities(&mut ExtBuilder::default().max_timestamp_drift(300).build(), || {
+		Timestamp::set_timestamp(42);
+		assert_eq!(Timestamp::check_inherent(&Call::set(400), &inherent_data(100)), Ok(()));
+		assert_eq!(
+			Timestamp::check_inherent(&Call::set(401), &inherent_data(100)),
+			Err(InherentError::TooFarInFuture { submitted: 401, max_allowed: 400 }),
+		);
+	});
+}
+
+mod check_inherent_properties {
+	use super::*;
+
+	#[derive(Debug, PartialEq)]
+	enum Outcome {
+		Accept,
+		Retry(u64),
+		Lagging,
+		Reject,
+	}
+
+	impl From<result::Result<(), InherentError>> for Outcome {
+		fn from(result: result::Result<(), InherentError>) -> Self {
+			match result {
+				Ok(()) => Outcome::Accept,
+				Err(InherentError::ValidAtTimestamp(minimum)) => Outcome::Retry(minimum),
+				Err(InherentError::TooFarInPast { .. }) => Outcome::Lagging,
+				Err(_) => Outcome::Reject,
+			}
+		}
+	}
+
+	/// What `check_inherent` should decide, with the mock's `MinimumPeriod` and block period of 5.
+	fn expected(now: u64, tolerance: u64, drift: u64, past_periods: u32, data: u64, t: u64) -> Outcome {
+		let minimum = match now.checked_add(5) {
+			Some(minimum) => cmp::max(minimum.saturating_sub(tolerance), now),
+			None => return Outcome::Reject,
+		};
+		let past_drift = 5 * u64::from(past_periods);
+		if t > data.saturating_add(drift) {
+			Outcome::Reject
+		} else if t < minimum {
+			Outcome::Retry(minimum)
+		} else if past_drift != 0 && t < data.saturating_sub(past_drift) {
+			Outcome::Lagging
+		} else {
+			Outcome::Accept
+		}
+	}
+
+	fn check(now: u64, tolerance: u64, drift: u64, past_periods: u32, data: u64, t: u64) -> Outcome {
+		let mut ext = ExtBuilder::default()
+			.past_tolerance(tolerance)
+			.max_timestamp_drift(drift)
+			.max_past_drift_periods(past_periods)
+			.build();
+		with_externalities(&mut ext, || {
+			Timestamp::set_timestamp(now);
+			Timestamp::check_inherent(&Call::set(t), &inherent_data(data)).into()
+		})
+	}
+
+	/// Moments around the mock's genesis values and both ends of the range.
+	const MOMENTS: &[u64] = &[
+		0, 1, 4, 5, 6, 10, 42, 47, 1_000,
+		u64::max_value() - 5, u64::max_value() - 1, u64::max_value(),
+	];
+
+	const TOLERANCES: &[u64] = &[0, 3, 9];
+	const DRIFTS: &[u64] = &[0, 1, 10];
+
+	#[test]
+	fn outcome_matches_model() {
+		for &now in MOMENTS {
+			for &tolerance in TOLERANCES {
+				for &drift in DRIFTS {
+					for &past_periods in &[0, 2] {
+						for &data in MOMENTS {
+							for &t in MOMENTS {
+								assert_eq!(
+									check(now, tolerance, drift, past_periods, data, t),
+									expected(now, tolerance, drift, past_periods, data, t),
+									"now {}, tolerance {}, drift {}, past periods {}, data {}, t {}",
+									now, tolerance, drift, past_periods, data, t,
+								);
+							}
+						}
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn retrying_at_minimum_never_retries_again() {
+		for &now in MOMENTS {
+			for &tolerance in TOLERANCES {
+				for &drift in DRIFTS {
+					for &data in MOMENTS {
+						for &t in MOMENTS {
+							if let Outcome::Retry(minimum) = check(now, tolerance, drift, 0, data, t) {
+								assert!(minimum > t);
+								assert_ne!(check(now, tolerance, drift, 0, data, minimum), Outcome::Retry(minimum));
+							}
+						}
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn accepted_region_is_contiguous() {
+		for &now in MOMENTS {
+			for &drift in DRIFTS {
+				for &data in MOMENTS {
+					for &t in MOMENTS {
+						if check(now, 0, drift, 0, data, t) == Outcome::Accept && t < data.saturating_add(drift) {
+							assert_eq!(check(now, 0, drift, 0, data, t + 1), Outcome::Accept);
+						}
+					}
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,99 @@
+// Copyright 2017-2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Timestamp inherent data provider also attaching the uptime of the authoring node.
+
+use std::time::Instant;
+use inherents::{RuntimeString, InherentIdentifier, InherentData, ProvideInherentData};
+use crate::{INHERENT_IDENTIFIER, UPTIME_INHERENT_IDENTIFIER, InherentDataProvider};
+
+/// Auxiliary trait to extract the uptime attached by `UptimeInherentDataProvider`.
+pub trait UptimeInherentData {
+	/// Get the uptime of the authoring node in milliseconds, if it was provided.
+	fn uptime_inherent_data(&self) -> Result<Option<u64>, RuntimeString>;
+}
+
+impl UptimeInherentData for InherentData {
+	fn uptime_inherent_data(&self) -> Result<Option<u64>, RuntimeString> {
+		self.get_data(&UPTIME_INHERENT_IDENTIFIER)
+	}
+}
+
+/// Provides the timestamp inherent like `InherentDataProvider`, along with the time elapsed on the
+/// node's monotonic clock since the provider was created, under `UPTIME_INHERENT_IDENTIFIER`.
+///
+/// The runtime ignores the uptime, but tooling can compare it to the wall-clock timestamps of the
+/// blocks authored by the node to detect resets of its clock.
+pub struct UptimeInherentDataProvider {
+	timestamp: InherentDataProvider,
+	started: Instant,
+}
+
+impl Default for UptimeInherentDataProvider {
+	fn default() -> Self {
+		Self::new(InherentDataProvider::default())
+	}
+}
+
+impl UptimeInherentDataProvider {
+	/// Create a provider attaching the uptime to the timestamp provided by `timestamp`, counting
+	/// from now.
+	pub fn new(timestamp: InherentDataProvider) -> Self {
+		UptimeInherentDataProvider { timestamp, started: Instant::now() }
+	}
+}
+
+impl ProvideInherentData for UptimeInherentDataProvider {
+	fn inherent_identifier(&self) -> &'static InherentIdentifier {
+		&INHERENT_IDENTIFIER
+	}
+
+	fn provide_inherent_data(&self, inherent_data: &mut InherentData) -> Result<(), RuntimeString> {
+		self.timestamp.provide_inherent_data(inherent_data)?;
+
+		let uptime = self.started.elapsed().as_millis() as u64;
+		inherent_data.put_data(UPTIME_INHERENT_IDENTIFIER, &uptime)
+	}
+
+	fn error_to_string(&self, error: &[u8]) -> Option<String> {
+		self.timestamp.error_to_string(error)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Duration;
+	use crate::TimestampInherentData;
+
+	#[test]
+	fn provides_timestamp_and_uptime() {
+		let provider = UptimeInherentDataProvider::default();
+		std::thread::sleep(Duration::from_millis(5));
+
+		let mut data = InherentData::new();
+		provider.provide_inherent_data(&mut data).unwrap();
+		assert!(data.timestamp_inherent_data().unwrap() > 0);
+		assert!(data.uptime_inherent_data().unwrap().unwrap() >= 5);
+	}
+
+	#[test]
+	fn uptime_is_optional() {
+		let mut data = InherentData::new();
+		InherentDataProvider::default().provide_inherent_data(&mut data).unwrap();
+		assert_eq!(data.uptime_inherent_data(), Ok(None));
+	}
+}